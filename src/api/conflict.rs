@@ -0,0 +1,148 @@
+use std::{collections::HashSet, time::SystemTime};
+
+use futures::TryStreamExt;
+
+use crate::*;
+
+// What to do about the name of a file that is about to be put into a directory.
+pub(crate) enum Resolution {
+    // go ahead with this name, which is free now
+    Create(String),
+    // leave the directory as it is, this file already has the name
+    Skip(Box<File>),
+    // put the file under the free name `temp` first, then `replace` the existing file with it
+    Replace { existing: Box<File>, temp: String },
+}
+
+// Applies `policy` if `dir_id` already has a file named `name`. `incoming` is the file that is
+// copied, moved or renamed there, or `None` for a new file.
+pub(crate) async fn resolve(
+    dir_id: &FileId,
+    name: &str,
+    incoming: Option<&File>,
+    policy: &ConflictPolicy,
+) -> Result<Resolution> {
+    let FileId(source, id) = dir_id;
+    let backend = backend_for(source).await?;
+
+    let existing = match backend.find(id, name).await? {
+        Some(f) if incoming.is_none_or(|i| i.id != f.id) => f,
+        _ => return Ok(Resolution::Create(name.to_owned())),
+    };
+
+    let answer;
+    let policy = match policy {
+        ConflictPolicy::Ask(ask) => {
+            answer = ask(Conflict {
+                existing: existing.clone(),
+                incoming: incoming.cloned(),
+            })
+            .await;
+            &answer
+        }
+        policy => policy,
+    };
+
+    let overwrite = match policy {
+        ConflictPolicy::Fail | ConflictPolicy::Ask(_) => {
+            return Err(Error::already_exists(format!(
+                "A file with name '{}' already exists!",
+                name
+            )))
+        }
+        ConflictPolicy::Skip => false,
+        ConflictPolicy::Overwrite => true,
+        ConflictPolicy::OverwriteIfNewer => {
+            let modified = incoming.map_or(Some(SystemTime::now()), |i| i.metadata.modified);
+            match (modified, existing.metadata.modified) {
+                (Some(incoming), Some(existing)) => incoming > existing,
+                _ => true,
+            }
+        }
+        ConflictPolicy::OverwriteIfDifferentSize => incoming.map_or(0, |i| i.size) != existing.size,
+        ConflictPolicy::KeepBoth => {
+            let free = free_name(&*backend, id, (1..).map(|n| numbered_name(name, n))).await?;
+            return Ok(Resolution::Create(free));
+        }
+    };
+
+    if !overwrite {
+        return Ok(Resolution::Skip(Box::new(existing)));
+    }
+
+    if existing.file_type == FileType::Dir {
+        let options = ListOptions::default();
+        let mut entries = backend.list(&existing.id.1, &options);
+        if entries.try_next().await?.is_some() {
+            return Err(Error::already_exists(format!(
+                "A directory with name '{}' already exists and is not empty!",
+                name
+            )));
+        }
+    }
+
+    let temp = format!(".{name}.partial");
+    let candidates = std::iter::once(temp.clone()).chain((1..).map(|n| numbered_name(&temp, n)));
+    Ok(Resolution::Replace {
+        existing: Box::new(existing),
+        temp: free_name(&*backend, id, candidates).await?,
+    })
+}
+
+// Replaces `existing` in `dir_id` with `file`, which was put there under a temporary name once it
+// was complete, returning `file` under the name of `existing`.
+pub(crate) async fn replace(dir_id: &FileId, existing: &File, file: &File) -> Result<File> {
+    let FileId(source, id) = dir_id;
+    let backend = backend_for(source).await?;
+
+    match existing.file_type {
+        FileType::Dir => backend.delete_dir(&existing.id.1).await?,
+        _ => backend.delete_file(&existing.id.1).await?,
+    }
+    backend.rename(&file.id.1, &existing.name).await?;
+
+    // local files are identified by their path, which the rename changed
+    backend.find(id, &existing.name).await?.ok_or_else(|| {
+        Error::not_found(format!(
+            "'{}' was replaced but could not be found",
+            existing.name
+        ))
+    })
+}
+
+// The first of `candidates` that no file in `dir_id` has.
+async fn free_name(
+    backend: &dyn Backend,
+    dir_id: &str,
+    mut candidates: impl Iterator<Item = String>,
+) -> Result<String> {
+    let names = backend
+        .list(dir_id, &ListOptions::default())
+        .map_ok(|f| f.name)
+        .try_collect::<HashSet<_>>()
+        .await?;
+
+    Ok(candidates.find(|n| !names.contains(n)).unwrap())
+}
+
+// `name` with ` (n)` added before its extension. A leading dot starts the name, not an extension.
+pub(crate) fn numbered_name(name: &str, n: u32) -> String {
+    match name.rfind('.') {
+        Some(i) if i > 0 => format!("{} ({n}){}", &name[..i], &name[i..]),
+        _ => format!("{name} ({n})"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_numbered_name() {
+        assert_eq!(super::numbered_name("notes.txt", 1), "notes (1).txt");
+        assert_eq!(
+            super::numbered_name("archive.tar.gz", 2),
+            "archive.tar (2).gz"
+        );
+        assert_eq!(super::numbered_name("notes", 3), "notes (3)");
+        assert_eq!(super::numbered_name(".bashrc", 1), ".bashrc (1)");
+    }
+}
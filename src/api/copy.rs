@@ -0,0 +1,954 @@
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use async_stream::{stream, try_stream};
+use futures::{stream, Stream, StreamExt, TryStreamExt};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+use super::{
+    conflict::{replace, resolve, Resolution},
+    create_unchecked, read, read_range, write,
+};
+use crate::{
+    hash::{HashReader, Hasher},
+    *,
+};
+
+// How often the checkpoint of a copy is written, a resumed copy carries on where the destination
+// says it stopped anyway.
+const CHECKPOINT_INTERVAL: Duration = Duration::from_secs(1);
+
+enum Event<'a> {
+    Bytes(&'a File, u64),
+    Done(&'a File),
+}
+
+/// Copies the file `file_id` into `dir_id` as `name`.
+///
+/// Within one file source that can copy files itself, like a single Google Drive account, the file
+/// source makes the copy without the contents passing through this process. Such a copy emits no
+/// `Progress` events. Otherwise, or to keep a [`CopyOptions::checkpoint`] or to hash a source whose
+/// file source keeps no checksum for [`CopyOptions::verify`], the file is read and written here.
+///
+/// The stream never ends with an error, failures are reported as [`CopyProgress::Failed`]. If
+/// `dir_id` already has a file named `name` the copy fails, see [`CopyOptions::conflict`] for
+/// other ways to handle that.
+pub fn copy_to_dir<'a>(
+    file_id: &'a FileId,
+    name: &'a str,
+    dir_id: &'a FileId,
+) -> impl Stream<Item = CopyProgress> + 'a {
+    copy_to_dir_with(file_id, name, dir_id, CopyOptions::default())
+}
+
+/// [`copy_to_dir`] with options to cancel or pause the copy, or to handle an existing file named
+/// `name`.
+pub fn copy_to_dir_with<'a>(
+    file_id: &'a FileId,
+    name: &'a str,
+    dir_id: &'a FileId,
+    options: CopyOptions,
+) -> impl Stream<Item = CopyProgress> + 'a {
+    stream! {
+        let src = match get(file_id).await {
+            Ok(src) => src,
+            Err(e) => {
+                yield CopyProgress::Failed(e);
+                return;
+            }
+        };
+
+        let (name, existing) = match resolve(dir_id, name, Some(&src), &options.conflict).await {
+            Ok(Resolution::Create(name)) => (name, None),
+            Ok(Resolution::Replace { existing, temp }) => (temp, Some(existing)),
+            Ok(Resolution::Skip(existing)) => {
+                yield CopyProgress::Skipped(*existing);
+                return;
+            }
+            Err(e) => {
+                yield CopyProgress::Failed(e);
+                return;
+            }
+        };
+
+        for await p in copy_file_to_dir(&src, &name, dir_id, &options) {
+            match (p, &existing) {
+                (CopyProgress::Completed(mut status), Some(existing)) => {
+                    match replace(dir_id, existing, &status.file).await {
+                        Ok(file) => {
+                            status.file = file;
+                            yield CopyProgress::Completed(status);
+                        }
+                        Err(e) => yield CopyProgress::Failed(e),
+                    }
+                }
+                (p, _) => yield p,
+            }
+        }
+    }
+}
+
+/// Continues the copy that `checkpoint` was kept for, see [`CopyOptions::checkpoint`].
+///
+/// The copy carries on where the destination says it stopped, which may be before
+/// [`Checkpoint::bytes_done`], reading only the rest of the source. As the bytes copied before are
+/// not read again, [`CopyOptions::verify`] then compares the checksums of the source and the
+/// whole copy once it is done.
+pub fn resume_copy(
+    checkpoint: Checkpoint,
+    options: CopyOptions,
+) -> impl Stream<Item = CopyProgress> {
+    stream! {
+        let files = futures::future::try_join(get(&checkpoint.source), get(&checkpoint.dest)).await;
+        let (src, dest) = match files {
+            Ok(files) => files,
+            Err(e) => {
+                yield CopyProgress::Failed(e);
+                return;
+            }
+        };
+
+        for await p in copy_file(&src, dest, Some(&checkpoint), &options) {
+            yield p;
+        }
+    }
+}
+
+// `copy_to_dir` for a source that was already fetched, to a name that is free.
+pub(crate) fn copy_file_to_dir<'a>(
+    src: &'a File,
+    name: &'a str,
+    dir_id: &'a FileId,
+    options: &'a CopyOptions,
+) -> impl Stream<Item = CopyProgress> + 'a {
+    stream! {
+        if options.cancel.is_cancelled() {
+            let e = Error::cancelled(format!("Copying '{}' was cancelled", src.id.1));
+            yield CopyProgress::Failed(e);
+            return;
+        }
+
+        if src.id.0 == dir_id.0 && !copies_through_process(src, options) {
+            let backend = match backend_for(&dir_id.0).await {
+                Ok(b) => b,
+                Err(e) => {
+                    yield CopyProgress::Failed(e);
+                    return;
+                }
+            };
+
+            let mut events = backend.copy_with_progress(&src.id.1, name, &dir_id.1);
+            let mut meter = Meter::new(0);
+            let mut status: Option<CopyStatus> = None;
+
+            loop {
+                let next = tokio::select! {
+                    biased;
+                    _ = options.cancel.cancelled() => None,
+                    e = async {
+                        options.pause.wait().await;
+                        events.next().await
+                    } => Some(e),
+                };
+
+                let event = match next {
+                    Some(Some(event)) => event,
+                    Some(None) => Err(anyhow::anyhow!(
+                        "the copy of '{}' ended before it was done",
+                        src.id.1
+                    )
+                    .into()),
+                    None => Err(Error::cancelled(format!("Copying '{}' was cancelled", src.id.1))),
+                };
+
+                let (file, bytes_done, done) = match event {
+                    // the file source cannot copy the file itself, so it is copied through this
+                    // process
+                    Err(Error::Unsupported { .. }) if status.is_none() => break,
+                    Err(e) => {
+                        if let Some(status) = &status {
+                            // the failure is what gets reported, not whether cleaning up worked
+                            let _ = delete_file(&status.file.id).await;
+                        }
+                        yield CopyProgress::Failed(e);
+                        return;
+                    }
+                    Ok(CopyEvent::Copying { file, bytes_done }) => (file, bytes_done, false),
+                    Ok(CopyEvent::Done(file)) => (file, src.size, true),
+                };
+
+                let started = status.is_none();
+                let status = status.get_or_insert_with(|| CopyStatus {
+                    file: file.clone(),
+                    bytes_done: 0,
+                    bytes_total: src.size,
+                    speed: 0.0,
+                    average_speed: 0.0,
+                    elapsed: Default::default(),
+                });
+                if started {
+                    yield CopyProgress::Started(status.clone());
+                }
+
+                status.file = file;
+                meter.update(status, bytes_done);
+
+                if !done {
+                    if !started || bytes_done > 0 {
+                        yield CopyProgress::Progress(status.clone());
+                    }
+                    continue;
+                }
+
+                if let Some(algorithm) = options.verify {
+                    if let Err(e) = verify_copy(src, &status.file, algorithm).await {
+                        yield CopyProgress::Failed(e);
+                        return;
+                    }
+                }
+
+                yield CopyProgress::Completed(status.clone());
+                return;
+            }
+        }
+
+        let dest = match create_unchecked(&FileType::File, name, dir_id).await {
+            Ok(f) => f,
+            Err(e) => {
+                yield CopyProgress::Failed(e);
+                return;
+            }
+        };
+
+        for await p in copy_file(src, dest, None, options) {
+            yield p;
+        }
+    }
+}
+
+// Whether `src` is copied through this process even where its file source could copy it itself,
+// which is where checkpoints are kept and copied bytes are hashed.
+fn copies_through_process(src: &File, options: &CopyOptions) -> bool {
+    let unhashed = options
+        .verify
+        .is_some_and(|algorithm| kept_checksum(src, algorithm).is_none());
+    keeps_checkpoint(options) || unhashed
+}
+
+// Copies `src` to the existing file `dest`, continuing the copy `resume` if given.
+fn copy_file<'a>(
+    src: &'a File,
+    dest: File,
+    resume: Option<&'a Checkpoint>,
+    options: &'a CopyOptions,
+) -> impl Stream<Item = CopyProgress> + 'a {
+    stream! {
+        let hasher = options
+            .verify
+            .map(|algorithm| Arc::new(Mutex::new(Hasher::new(algorithm))));
+
+        let resumable = resume.is_some() || keeps_checkpoint(options);
+        let opened = open(src, &dest.id, resume, hasher, options).await;
+        let (reader, writer, mut checkpoint, hasher) = match opened {
+            Ok(opened) => opened,
+            Err(e) => {
+                clean_up(&dest.id, &e, resumable, options).await;
+                yield CopyProgress::Failed(e);
+                return;
+            }
+        };
+        let mut saved = Instant::now();
+
+        let mut meter = Meter::new(checkpoint.bytes_done);
+        let mut status = CopyStatus {
+            file: dest,
+            bytes_done: checkpoint.bytes_done,
+            bytes_total: src.size,
+            speed: 0.0,
+            average_speed: 0.0,
+            elapsed: Default::default(),
+        };
+
+        yield CopyProgress::Started(status.clone());
+
+        for await bytes in transfer(reader, writer, &src.id, options) {
+            let bytes = match bytes {
+                Ok(bytes) => bytes,
+                Err(e) => {
+                    clean_up(&status.file.id, &e, resumable, options).await;
+                    yield CopyProgress::Failed(e);
+                    return;
+                }
+            };
+
+            let bytes_done = status.bytes_done + bytes;
+            meter.update(&mut status, bytes_done);
+
+            if saved.elapsed() >= CHECKPOINT_INTERVAL {
+                checkpoint.bytes_done = status.bytes_done;
+                if let Err(e) = save_checkpoint(&checkpoint, options).await {
+                    clean_up(&status.file.id, &e, resumable, options).await;
+                    yield CopyProgress::Failed(e);
+                    return;
+                }
+                saved = Instant::now();
+            }
+
+            yield CopyProgress::Progress(status.clone());
+        }
+
+        if let Err(e) = remove_checkpoint(options).await {
+            yield CopyProgress::Failed(e);
+            return;
+        }
+
+        let file = async {
+            let file = get(&status.file.id).await?;
+
+            match (options.verify, hasher) {
+                (Some(algorithm), Some(hasher)) => {
+                    let digest = hasher.lock().unwrap().clone().finish();
+                    verify(src, &file, algorithm, &digest).await?;
+                }
+                // a resumed copy did not read what was copied before
+                (Some(algorithm), None) => verify_copy(src, &file, algorithm).await?,
+                _ => {}
+            }
+
+            Ok::<_, Error>(file)
+        };
+
+        match file.await {
+            Ok(file) => {
+                status.file = file;
+                yield CopyProgress::Completed(status);
+            }
+            Err(e) => yield CopyProgress::Failed(e),
+        }
+    }
+}
+
+// Compares `digest`, the checksum of the bytes read from `src`, with the checksums of `src` and
+// its copy `dest`.
+//
+// Checksums kept by the file sources are used where there are any. Only if neither has one `dest`
+// is read again to hash it, as otherwise nothing could tell a bad write.
+async fn verify(src: &File, dest: &File, algorithm: Checksum, digest: &str) -> Result<()> {
+    let src_sum = kept_checksum(src, algorithm);
+    let dest_sum = match kept_checksum(dest, algorithm) {
+        None if src_sum.is_none() => {
+            let FileId(source, id) = &dest.id;
+            backend_for(source).await?.checksum(id, algorithm).await?
+        }
+        sum => sum,
+    };
+
+    if src_sum.is_none() && dest_sum.is_none() {
+        return Err(Error::unsupported(format!(
+            "Neither '{}' nor its copy have a {algorithm:?} checksum",
+            src.name
+        )));
+    }
+
+    for (f, sum) in [(src, src_sum), (dest, dest_sum)] {
+        match sum {
+            Some(sum) if !sum.eq_ignore_ascii_case(digest) => {
+                return Err(Error::ChecksumMismatch {
+                    message: format!(
+                        "The {algorithm:?} checksum of '{}' is {sum} instead of {digest}",
+                        f.name
+                    ),
+                    source: None,
+                })
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+// Compares the checksums of `src` and `dest`, a copy whose bytes were not hashed as they were
+// written, so `dest` is read again to hash it if its file source keeps no checksum of it.
+pub(crate) async fn verify_copy(src: &File, dest: &File, algorithm: Checksum) -> Result<()> {
+    let sums = futures::future::try_join(
+        file_checksum(src, algorithm),
+        file_checksum(dest, algorithm),
+    );
+
+    match sums.await? {
+        (Some(src_sum), Some(dest_sum)) if !src_sum.eq_ignore_ascii_case(&dest_sum) => {
+            Err(Error::ChecksumMismatch {
+                message: format!(
+                    "The {algorithm:?} checksum of '{}' is {dest_sum} instead of {src_sum}",
+                    dest.name
+                ),
+                source: None,
+            })
+        }
+        (Some(_), Some(_)) => Ok(()),
+        _ => Err(Error::unsupported(format!(
+            "'{}' or its copy has no {algorithm:?} checksum",
+            src.name
+        ))),
+    }
+}
+
+// The checksum the file source keeps of `f`, or otherwise the one it computes.
+pub(crate) async fn file_checksum(f: &File, algorithm: Checksum) -> Result<Option<String>> {
+    if let Some(sum) = kept_checksum(f, algorithm) {
+        return Ok(Some(sum));
+    }
+
+    let FileId(source, id) = &f.id;
+    backend_for(source).await?.checksum(id, algorithm).await
+}
+
+// The algorithm to compare `a` and `b` with, one that their file sources keep checksums for if
+// possible.
+pub(crate) fn checksum_algorithm(a: &File, b: &File) -> Checksum {
+    match (&a.metadata, &b.metadata) {
+        (a, b) if a.md5.is_some() || b.md5.is_some() => Checksum::Md5,
+        (a, b) if a.sha256.is_some() || b.sha256.is_some() => Checksum::Sha256,
+        _ => Checksum::Md5,
+    }
+}
+
+fn kept_checksum(f: &File, algorithm: Checksum) -> Option<String> {
+    match algorithm {
+        Checksum::Md5 => f.metadata.md5.clone(),
+        Checksum::Sha256 => f.metadata.sha256.clone(),
+    }
+}
+
+// Opens `src` and `dest` for a copy, skipping what an earlier copy already wrote to `dest` when
+// the copy is resumable.
+//
+// The hasher is returned along with the reader it hashes, which is only the case if `src` is read
+// from its beginning.
+async fn open(
+    src: &File,
+    dest: &FileId,
+    resume: Option<&Checkpoint>,
+    hasher: Option<Arc<Mutex<Hasher>>>,
+    options: &CopyOptions,
+) -> Result<Opened> {
+    let mut checkpoint = resume.cloned().unwrap_or_else(|| Checkpoint {
+        source: src.id.clone(),
+        dest: dest.clone(),
+        session: None,
+        bytes_done: 0,
+    });
+    let hashed = |reader: BoxedAsyncRead<'static>| match &hasher {
+        Some(h) => Box::pin(HashReader::new(reader, h.clone())) as BoxedAsyncRead<'static>,
+        None => reader,
+    };
+
+    if resume.is_none() && !keeps_checkpoint(options) {
+        let (reader, writer) = futures::future::try_join(read(&src.id), write(dest)).await?;
+        return Ok((hashed(reader), writer, checkpoint, hasher));
+    }
+
+    // fails before anything is written if checkpoints cannot be kept
+    save_checkpoint(&checkpoint, options).await?;
+
+    let w = backend_for(&dest.0)
+        .await?
+        .write_resumable(&dest.1, checkpoint.session.as_deref())
+        .await?;
+
+    checkpoint.session = w.session;
+    checkpoint.bytes_done = w.offset;
+    save_checkpoint(&checkpoint, options).await?;
+
+    if w.offset == 0 {
+        return Ok((hashed(read(&src.id).await?), w.writer, checkpoint, hasher));
+    }
+    if w.offset > src.size {
+        return Err(Error::VerificationFailed {
+            message: format!(
+                "'{}' is smaller than what was already copied of it",
+                src.name
+            ),
+            source: None,
+        });
+    }
+
+    let reader = read_range(&src.id, w.offset, src.size - w.offset).await?;
+    Ok((reader, w.writer, checkpoint, None))
+}
+
+type Opened = (
+    BoxedAsyncRead<'static>,
+    BoxedAsyncWrite<'static>,
+    Checkpoint,
+    Option<Arc<Mutex<Hasher>>>,
+);
+
+fn keeps_checkpoint(options: &CopyOptions) -> bool {
+    options.checkpoint.is_some()
+}
+
+// Deletes what the failed copy wrote to `dest`, unless it is kept to be resumed.
+async fn clean_up(dest: &FileId, error: &Error, resumable: bool, options: &CopyOptions) {
+    let lost = matches!(error, Error::Cancelled { .. } | Error::Unsupported { .. });
+    if resumable && !lost {
+        return;
+    }
+
+    // the failure is what gets reported, not whether cleaning up worked
+    let _ = delete_file(dest).await;
+    let _ = remove_checkpoint(options).await;
+}
+
+#[cfg(feature = "serde")]
+async fn save_checkpoint(checkpoint: &Checkpoint, options: &CopyOptions) -> Result<()> {
+    match &options.checkpoint {
+        Some(path) => checkpoint.save(path).await,
+        None => Ok(()),
+    }
+}
+
+#[cfg(not(feature = "serde"))]
+async fn save_checkpoint(_checkpoint: &Checkpoint, options: &CopyOptions) -> Result<()> {
+    match &options.checkpoint {
+        Some(_) => Err(Error::unsupported(
+            "keeping copy checkpoints needs the `serde` feature",
+        )),
+        None => Ok(()),
+    }
+}
+
+async fn remove_checkpoint(options: &CopyOptions) -> Result<()> {
+    let Some(path) = &options.checkpoint else {
+        return Ok(());
+    };
+
+    match tokio::fs::remove_file(path).await {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e)
+            .with_context(|| format!("Could not remove checkpoint '{}'", path.to_string_lossy())),
+        _ => Ok(()),
+    }
+}
+
+// Copies the contents of `file_id` to the existing file `dest_id`, yielding the number of bytes
+// copied with each buffer.
+pub(crate) fn copy_into<'a>(
+    file_id: &'a FileId,
+    dest_id: &'a FileId,
+    options: &'a CopyOptions,
+) -> impl Stream<Item = Result<u64>> + 'a {
+    try_stream! {
+        let (r, w) = futures::future::try_join(read(file_id), write(dest_id)).await?;
+
+        for await bytes in transfer(r, w, file_id, options) {
+            yield bytes?;
+        }
+    }
+}
+
+// Copies everything from `r` to `w`, yielding the number of bytes copied with each buffer.
+fn transfer<'a>(
+    r: BoxedAsyncRead<'static>,
+    w: BoxedAsyncWrite<'static>,
+    file_id: &'a FileId,
+    options: &'a CopyOptions,
+) -> impl Stream<Item = Result<u64>> + 'a {
+    try_stream! {
+        let mut reader = tokio::io::BufReader::new(r);
+        let mut writer = tokio::io::BufWriter::new(w);
+
+        let mut buf = vec!(0u8; 5 * 1024 * 1024);
+
+        loop {
+            let chunk = async {
+                options.pause.wait().await;
+
+                let bytes = reader.read(&mut buf).await?;
+
+                writer.write_all(&buf[..bytes]).await?;
+
+                Ok::<_, Error>(bytes)
+            };
+
+            let bytes = tokio::select! {
+                biased;
+                _ = options.cancel.cancelled() => {
+                    Err(Error::cancelled(format!("Copying '{}' was cancelled", file_id.1)))
+                }
+                bytes = chunk => bytes,
+            }?;
+
+            if bytes == 0 {
+                break;
+            }
+
+            yield bytes as u64;
+        }
+        writer.shutdown().await?;
+    }
+}
+
+// Measures the throughput of a copy.
+struct Meter {
+    start: Instant,
+    last: Instant,
+    // the bytes that were already copied when the copy started
+    initial: u64,
+}
+
+impl Meter {
+    fn new(initial: u64) -> Self {
+        let now = Instant::now();
+
+        Self {
+            start: now,
+            last: now,
+            initial,
+        }
+    }
+
+    // Updates `status` for `bytes_done` bytes having been copied by now.
+    fn update(&mut self, status: &mut CopyStatus, bytes_done: u64) {
+        let now = Instant::now();
+
+        if bytes_done > status.bytes_done {
+            status.speed = per_second(bytes_done - status.bytes_done, now - self.last);
+            self.last = now;
+        }
+
+        status.bytes_done = bytes_done;
+        status.elapsed = now - self.start;
+        status.average_speed = per_second(bytes_done.saturating_sub(self.initial), status.elapsed);
+    }
+}
+
+fn per_second(bytes: u64, time: Duration) -> f64 {
+    match time.as_secs_f64() {
+        secs if secs > 0.0 => bytes as f64 / secs,
+        _ => 0.0,
+    }
+}
+
+/// Copies the directory `dir_id` and everything in it into `parent_id` as `name`.
+///
+/// The source and the destination may be on any pair of file sources. The directory structure,
+/// including empty directories, is recreated first, then files are copied with up to
+/// [`CopyOptions::concurrency`] of them at a time, the same way as [`copy_to_dir`] copies them.
+/// Only directories and regular files are copied, other entries like symbolic links are skipped.
+///
+/// If [`CopyOptions::conflict`] leaves an existing file named `name` as it is, nothing is copied
+/// and the stream ends without any events.
+pub fn copy_dir<'a>(
+    dir_id: &'a FileId,
+    name: &'a str,
+    parent_id: &'a FileId,
+    options: CopyOptions,
+) -> impl Stream<Item = Result<CopyDirProgress>> + 'a {
+    try_stream! {
+        let tree = Tree::scan(get(dir_id).await?).await?;
+        let (name, existing) = match resolve(parent_id, name, Some(&tree.dirs[0].0), &options.conflict).await? {
+            Resolution::Create(name) => (name, None),
+            Resolution::Replace { existing, temp } => (temp, Some(existing)),
+            Resolution::Skip(_) => return,
+        };
+        let dest = create_unchecked(&FileType::Dir, &name, parent_id).await?;
+
+        for await p in copy_tree(&tree, &dest.id, &options) {
+            match p {
+                Err(e @ Error::Cancelled { .. }) => {
+                    // the cancellation is what gets reported, not whether cleaning up worked
+                    let _ = delete_recursive(&dest.id, DeleteOptions::default())
+                        .try_for_each(|_| async { Ok(()) })
+                        .await;
+                    Err(e)?;
+                }
+                p => yield p?,
+            }
+        }
+
+        if let Some(existing) = existing {
+            replace(parent_id, &existing, &dest).await?;
+        }
+    }
+}
+
+// Copies everything in `tree` into the existing directory `dest_id`.
+pub(crate) fn copy_tree<'a>(
+    tree: &'a Tree,
+    dest_id: &'a FileId,
+    options: &'a CopyOptions,
+) -> impl Stream<Item = Result<CopyDirProgress>> + 'a {
+    try_stream! {
+        // a single checkpoint cannot track the files of a directory, which are copied at once
+        let options = &CopyOptions {
+            checkpoint: None,
+            ..options.clone()
+        };
+
+        let files_total = tree.files.len() as u64;
+        let bytes_total = tree.size();
+
+        // `dirs` lists parents before their children, so every parent is created before them
+        let mut created = Vec::<FileId>::with_capacity(tree.dirs.len());
+        for (d, parent) in tree.dirs.iter() {
+            if options.cancel.is_cancelled() {
+                Err(Error::cancelled(format!("Copying '{}' was cancelled", d.name)))?;
+            }
+
+            let new = match parent {
+                None => dest_id.clone(),
+                Some(p) => create_unchecked(&FileType::Dir, &d.name, &created[*p]).await?.id,
+            };
+            created.push(new);
+        }
+
+        let copies = stream::iter(tree.files.iter())
+            .map(|(f, parent)| {
+                copy_events(f, &created[*parent], options).boxed()
+            })
+            .flatten_unordered(options.concurrency.max(1));
+
+        let mut files_done = 0;
+        let mut bytes_done = 0;
+
+        for await e in copies {
+            let current = match e? {
+                Event::Bytes(f, bytes) => {
+                    bytes_done += bytes;
+                    f
+                }
+                Event::Done(f) => {
+                    files_done += 1;
+                    f
+                }
+            };
+
+            yield CopyDirProgress {
+                current: current.clone(),
+                files_done,
+                files_total,
+                bytes_done,
+                bytes_total,
+            };
+        }
+    }
+}
+
+// The bytes copied with each event of copying `f` into `dir`, followed by `Event::Done`.
+fn copy_events<'a>(
+    f: &'a File,
+    dir: &'a FileId,
+    options: &'a CopyOptions,
+) -> impl Stream<Item = Result<Event<'a>>> + 'a {
+    try_stream! {
+        let mut copied = 0;
+
+        for await p in copy_file_to_dir(f, &f.name, dir, options) {
+            match p {
+                CopyProgress::Started(_) => {}
+                CopyProgress::Progress(s) => {
+                    yield Event::Bytes(f, s.bytes_done - copied);
+                    copied = s.bytes_done;
+                }
+                CopyProgress::Completed(_) | CopyProgress::Skipped(_) => yield Event::Done(f),
+                CopyProgress::Failed(e) => Err(e)?,
+            }
+        }
+    }
+}
+
+/// Every directory and regular file in a tree, along with the index of their parent in `dirs`.
+pub(crate) struct Tree {
+    // in breadth first order starting with the root, which has no parent
+    pub dirs: Vec<(File, Option<usize>)>,
+    pub files: Vec<(File, usize)>,
+    // the entries that are neither files nor directories, like links, which are not copied
+    pub others: Vec<File>,
+}
+
+impl Tree {
+    pub async fn scan(root: File) -> Result<Tree> {
+        if root.file_type != FileType::Dir {
+            return Err(Error::not_a_directory(format!(
+                "'{}' is not a directory",
+                root.name
+            )));
+        }
+
+        let mut tree = Tree {
+            dirs: vec![],
+            files: vec![],
+            others: vec![],
+        };
+        let mut queue = VecDeque::from([(root, None)]);
+
+        while let Some((dir, parent)) = queue.pop_front() {
+            let entries = list(&dir.id).try_collect::<Vec<_>>().await?;
+            let index = tree.dirs.len();
+            tree.dirs.push((dir, parent));
+
+            for e in entries {
+                match e.file_type {
+                    FileType::Dir => queue.push_back((e, Some(index))),
+                    FileType::File => tree.files.push((e, index)),
+                    _ => tree.others.push(e),
+                }
+            }
+        }
+
+        Ok(tree)
+    }
+
+    /// The paths of the files in the tree relative to its root, along with the files.
+    pub fn file_paths(&self) -> impl Iterator<Item = (String, &File)> + '_ {
+        let mut dir_paths = Vec::<String>::with_capacity(self.dirs.len());
+        for (d, parent) in self.dirs.iter() {
+            let path = match parent {
+                None => String::new(),
+                Some(p) => format!("{}{}/", dir_paths[*p], d.name),
+            };
+            dir_paths.push(path);
+        }
+
+        self.files
+            .iter()
+            .map(move |(f, parent)| (format!("{}{}", dir_paths[*parent], f.name), f))
+    }
+
+    /// The total size of the files in the tree.
+    pub fn size(&self) -> u64 {
+        self.files.iter().map(|(f, _)| f.size).sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    #[tokio::test]
+    async fn test_verify_copy() {
+        let tmp = TempDir::new("verify-copy");
+        tmp.write("src.txt", "abc");
+        tmp.write("good.txt", "abc");
+        tmp.write("bad.txt", "abd");
+
+        let mut src = get(&tmp.id("src.txt")).await.unwrap();
+        let good = get(&tmp.id("good.txt")).await.unwrap();
+        let bad = get(&tmp.id("bad.txt")).await.unwrap();
+
+        verify_copy(&src, &good, Checksum::Md5).await.unwrap();
+        let e = verify_copy(&src, &bad, Checksum::Md5).await.unwrap_err();
+        assert!(matches!(e, Error::ChecksumMismatch { .. }), "{e}");
+
+        // the copy is hashed even when the source has a checksum kept, as Google Drive files do
+        src.metadata.md5 = Some("900150983cd24fb0d6963f7d28e17f72".into());
+        verify_copy(&src, &good, Checksum::Md5).await.unwrap();
+        let e = verify_copy(&src, &bad, Checksum::Md5).await.unwrap_err();
+        assert!(matches!(e, Error::ChecksumMismatch { .. }), "{e}");
+    }
+
+    // A copy of `src/a.txt` into `dest` that hashes what it copies, so it is not left to the file
+    // source, and starts paused.
+    fn paused_copy(tmp: &TempDir) -> (FileId, FileId, CopyOptions) {
+        tmp.write("src/a.txt", "a");
+        std::fs::create_dir(tmp.path("dest")).unwrap();
+
+        let options = CopyOptions {
+            verify: Some(Checksum::Md5),
+            ..Default::default()
+        };
+        options.pause.pause();
+        (tmp.id("src/a.txt"), tmp.id("dest"), options)
+    }
+
+    #[tokio::test]
+    async fn test_cancel() {
+        let tmp = TempDir::new("copy-cancel");
+        let (src, dest, options) = paused_copy(&tmp);
+        let s = copy_to_dir_with(&src, "a.txt", &dest, options.clone());
+        futures::pin_mut!(s);
+
+        assert!(matches!(s.next().await, Some(CopyProgress::Started(_))));
+        assert!(tmp.path("dest/a.txt").exists());
+
+        options.cancel.cancel();
+        let e = s.next().await;
+        assert!(
+            matches!(e, Some(CopyProgress::Failed(Error::Cancelled { .. }))),
+            "{e:?}"
+        );
+        assert!(s.next().await.is_none());
+        assert!(!tmp.path("dest/a.txt").exists());
+    }
+
+    #[tokio::test]
+    async fn test_pause() {
+        let tmp = TempDir::new("copy-pause");
+        let (src, dest, options) = paused_copy(&tmp);
+        let s = copy_to_dir_with(&src, "a.txt", &dest, options.clone());
+        futures::pin_mut!(s);
+
+        assert!(matches!(s.next().await, Some(CopyProgress::Started(_))));
+        let paused = tokio::time::timeout(Duration::from_millis(50), s.next()).await;
+        assert!(paused.is_err());
+
+        options.pause.resume();
+        let events = s.collect::<Vec<_>>().await;
+        assert!(
+            matches!(events.last(), Some(CopyProgress::Completed(_))),
+            "{events:?}"
+        );
+        assert_eq!(tmp.read("dest/a.txt").as_deref(), Some("a"));
+    }
+
+    #[cfg(feature = "serde")]
+    #[tokio::test]
+    async fn test_resume_copy() {
+        let tmp = TempDir::new("copy-resume");
+        tmp.write("src/a.txt", "abcdef");
+        std::fs::create_dir(tmp.path("dest")).unwrap();
+        let path = tmp.path("copy.checkpoint");
+
+        let options = CopyOptions {
+            checkpoint: Some(path.clone()),
+            verify: Some(Checksum::Md5),
+            ..Default::default()
+        };
+        options.pause.pause();
+
+        // the process stops after writing part of the copy
+        let (src, dir) = (tmp.id("src/a.txt"), tmp.id("dest"));
+        {
+            let s = copy_to_dir_with(&src, "a.txt", &dir, options);
+            futures::pin_mut!(s);
+            assert!(matches!(s.next().await, Some(CopyProgress::Started(_))));
+        }
+        let mut dest = std::fs::OpenOptions::new()
+            .append(true)
+            .open(tmp.path("dest/a.txt"))
+            .unwrap();
+        std::io::Write::write_all(&mut dest, b"abc").unwrap();
+
+        let checkpoint = Checkpoint::load(&path).await.unwrap();
+        assert_eq!(checkpoint.dest, tmp.id("dest/a.txt"));
+
+        let options = CopyOptions {
+            checkpoint: Some(path.clone()),
+            verify: Some(Checksum::Md5),
+            ..Default::default()
+        };
+        let events = resume_copy(checkpoint, options).collect::<Vec<_>>().await;
+        assert!(matches!(&events[0], CopyProgress::Started(s) if s.bytes_done == 3));
+        assert!(
+            matches!(events.last(), Some(CopyProgress::Completed(_))),
+            "{events:?}"
+        );
+        assert_eq!(tmp.read("dest/a.txt").as_deref(), Some("abcdef"));
+        assert!(!path.exists());
+    }
+}
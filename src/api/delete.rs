@@ -0,0 +1,127 @@
+use async_stream::try_stream;
+use futures::{Stream, TryStreamExt};
+
+use crate::*;
+
+struct Frame {
+    dir: File,
+    path: String,
+    // `None` until the directory has been listed
+    entries: Option<Vec<File>>,
+    // whether any entry of the directory could not be deleted
+    failed: bool,
+}
+
+impl Frame {
+    fn new(dir: File, path: String) -> Self {
+        Self {
+            dir,
+            path,
+            entries: None,
+            failed: false,
+        }
+    }
+}
+
+/// Deletes the directory `dir_id` together with everything in it.
+///
+/// Entries are deleted one at a time, children before their parents, on every file source, and
+/// each of them is reported as a [`DeleteProgress`]. Symbolic links are deleted, not followed. A
+/// directory is left in place if any entry in it could not be deleted.
+pub fn delete_recursive(
+    dir_id: &FileId,
+    options: DeleteOptions,
+) -> impl Stream<Item = Result<DeleteProgress>> + '_ {
+    try_stream! {
+        let FileId(source, id) = dir_id;
+        let backend = backend_for(source).await?;
+        let root = backend.get(id).await?;
+
+        if root.file_type != FileType::Dir {
+            Err(Error::not_a_directory(format!(
+                "'{}' is not a directory",
+                root.name
+            )))?;
+        }
+
+        let path = root.name.clone();
+        let mut stack = vec![Frame::new(root, path)];
+
+        while let Some(frame) = stack.last_mut() {
+            if frame.entries.is_none() {
+                let entries = backend
+                    .list(&frame.dir.id.1, &ListOptions::default())
+                    .try_collect::<Vec<_>>()
+                    .await;
+
+                match entries {
+                    Ok(entries) => frame.entries = Some(entries),
+                    Err(e) => {
+                        let frame = stack.pop().unwrap();
+                        let p = progress(frame.dir, frame.path, Err(e), &options)?;
+                        if let Some(parent) = stack.last_mut() {
+                            parent.failed = true;
+                        }
+                        yield p;
+                        continue;
+                    }
+                }
+            }
+
+            match frame.entries.as_mut().and_then(|e| e.pop()) {
+                Some(entry) if entry.file_type == FileType::Dir => {
+                    let path = format!("{}/{}", frame.path, entry.name);
+                    stack.push(Frame::new(entry, path));
+                }
+                Some(entry) => {
+                    let path = format!("{}/{}", frame.path, entry.name);
+                    let res = backend.delete_file(&entry.id.1).await.map(|_| entry.size);
+                    let p = progress(entry, path, res, &options)?;
+                    frame.failed |= p.error.is_some();
+                    yield p;
+                }
+                None => {
+                    let frame = stack.pop().unwrap();
+                    let res = match frame.failed {
+                        true => Err(Error::DirectoryNotEmpty {
+                            message: format!(
+                                "Could not delete directory '{}', some of its entries were not deleted",
+                                frame.path
+                            ),
+                            source: None,
+                        }),
+                        false => backend.delete_dir(&frame.dir.id.1).await.map(|_| 0),
+                    };
+                    let p = progress(frame.dir, frame.path, res, &options)?;
+                    if let (Some(parent), Some(_)) = (stack.last_mut(), &p.error) {
+                        parent.failed = true;
+                    }
+                    yield p;
+                }
+            }
+        }
+    }
+}
+
+fn progress(
+    file: File,
+    path: String,
+    bytes_freed: Result<u64>,
+    options: &DeleteOptions,
+) -> Result<DeleteProgress> {
+    match bytes_freed {
+        Ok(bytes_freed) => Ok(DeleteProgress {
+            file,
+            path,
+            bytes_freed,
+            error: None,
+        }),
+        Err(e) if options.continue_on_error => Ok(DeleteProgress {
+            file,
+            path,
+            bytes_freed: 0,
+            error: Some(e),
+        }),
+        Err(e) => Err(e),
+    }
+}
@@ -0,0 +1,137 @@
+use std::collections::HashMap;
+
+use async_stream::stream;
+use futures::{Stream, TryStreamExt};
+
+use crate::*;
+
+/// Counts the size, files and directories in `dir_id` and all directories below it, reporting the
+/// totals so far with every directory found.
+///
+/// Several directories are listed at the same time, like [`walk`] does. A directory that cannot be
+/// listed is reported as an error and left out of the totals, the count carries on with the rest.
+pub fn du(dir_id: &FileId) -> impl Stream<Item = Result<DuProgress>> + '_ {
+    stream! {
+        let mut usage = DiskUsage::default();
+
+        for await e in walk(dir_id, WalkOptions::default()) {
+            match e {
+                Ok(e) if e.file.file_type == FileType::Dir => {
+                    usage.dirs += 1;
+                    yield Ok(DuProgress::Counting { path: e.path, usage });
+                }
+                Ok(e) => {
+                    usage.files += 1;
+                    usage.size += e.file.size;
+                }
+                Err(e) => yield Err(e),
+            }
+        }
+
+        yield Ok(DuProgress::Done(usage));
+    }
+}
+
+/// The disk usage of `dir_id` and every entry below it, keeping only the largest `max_children`
+/// entries of each directory.
+///
+/// Left out entries still count towards the usage of their directory. Unlike [`du`], this fails if
+/// any directory cannot be listed.
+pub async fn usage_tree(dir_id: &FileId, max_children: usize) -> Result<UsageTree> {
+    let root = get(dir_id).await?;
+    if root.file_type != FileType::Dir {
+        return Err(Error::not_a_directory(format!(
+            "'{}' is not a directory",
+            root.name
+        )));
+    }
+
+    // the directories by their ids, not their paths, as Drive names may contain `/` and a Drive
+    // folder may have several folders of the same name
+    let mut dirs = HashMap::new();
+    // in breadth first order, so parents come before their children
+    let mut nodes = vec![Node::new(root, None)];
+
+    let entries = walk(dir_id, WalkOptions::default());
+    futures::pin_mut!(entries);
+
+    while let Some(e) = entries.try_next().await? {
+        // the root may be known under another id than the one its entries name, like a path with
+        // a trailing `/` or the `root` alias on Drive
+        let parent = match e.depth {
+            1 => Some(0),
+            _ => e
+                .file
+                .parent_id
+                .as_ref()
+                .and_then(|p| dirs.get(&p.1).copied()),
+        };
+        let parent = parent.ok_or_else(|| {
+            Error::not_found(format!("The directory of '{}' was not found", e.path))
+        })?;
+        let index = nodes.len();
+
+        if e.file.file_type == FileType::Dir {
+            dirs.insert(e.file.id.1.clone(), index);
+        }
+        nodes[parent].children.push(index);
+        nodes.push(Node::new(e.file, Some(parent)));
+    }
+
+    for i in (1..nodes.len()).rev() {
+        let node = &nodes[i];
+        let (mut usage, parent) = (node.usage, node.parent.unwrap());
+        match node.file.as_ref().unwrap().file_type {
+            FileType::Dir => usage.dirs += 1,
+            _ => usage.files += 1,
+        }
+
+        let parent = &mut nodes[parent].usage;
+        parent.size += usage.size;
+        parent.files += usage.files;
+        parent.dirs += usage.dirs;
+    }
+
+    Ok(build(&mut nodes, 0, max_children))
+}
+
+struct Node {
+    file: Option<File>,
+    parent: Option<usize>,
+    usage: DiskUsage,
+    children: Vec<usize>,
+}
+
+impl Node {
+    fn new(file: File, parent: Option<usize>) -> Self {
+        let usage = match file.file_type {
+            FileType::Dir => DiskUsage::default(),
+            _ => DiskUsage {
+                size: file.size,
+                ..Default::default()
+            },
+        };
+
+        Self {
+            file: Some(file),
+            parent,
+            usage,
+            children: vec![],
+        }
+    }
+}
+
+fn build(nodes: &mut [Node], index: usize, max_children: usize) -> UsageTree {
+    let mut children = std::mem::take(&mut nodes[index].children);
+    children.sort_by_key(|c| std::cmp::Reverse(nodes[*c].usage.size));
+    children.truncate(max_children);
+
+    UsageTree {
+        file: nodes[index].file.take().unwrap(),
+        usage: nodes[index].usage,
+        children: children
+            .into_iter()
+            .map(|c| build(nodes, c, max_children))
+            .collect(),
+    }
+}
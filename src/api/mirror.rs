@@ -0,0 +1,476 @@
+use std::{
+    collections::HashSet,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use async_stream::try_stream;
+use futures::Stream;
+
+use super::{
+    conflict::numbered_name,
+    mv::move_file,
+    tree::{ancestors, copy_over, forget, join, remove, same_contents, scan, split, Creates, Tree},
+};
+use crate::*;
+
+/// Plans what [`mirror`] would do to make `dst` look like `src`, without changing anything.
+pub async fn mirror_plan(
+    src: &FileId,
+    dst: &FileId,
+    options: &MirrorOptions,
+) -> Result<MirrorPlan> {
+    let trees = [scan(src).await?, scan(dst).await?];
+    plan(&trees, options, SystemTime::now()).await
+}
+
+/// Makes the directory `dst` look like `src`, on any pair of file sources.
+///
+/// Directories and files that are only in the source are created in the destination, and files
+/// that differ by [`MirrorOptions::compare`] are copied over the ones there. Files that are only
+/// in the destination, or that are a directory on one side and a file on the other, are kept,
+/// deleted or archived by [`MirrorOptions::extraneous`]. The source is never changed.
+///
+/// The first event is the [`MirrorPlan`], followed by every action once it was carried out.
+pub fn mirror<'a>(
+    src: &'a FileId,
+    dst: &'a FileId,
+    options: MirrorOptions,
+) -> impl Stream<Item = Result<MirrorProgress>> + 'a {
+    try_stream! {
+        let trees = [scan(src).await?, scan(dst).await?];
+        let plan = plan(&trees, &options, SystemTime::now()).await?;
+
+        yield MirrorProgress::Planned(plan.clone());
+
+        let mut mirror = Mirror {
+            roots: [src, dst],
+            trees,
+        };
+
+        for action in plan.actions {
+            if options.cancel.is_cancelled() {
+                Err(Error::cancelled(format!("Mirroring '{}' was cancelled", src.1)))?;
+            }
+
+            mirror.apply(&action, &options).await?;
+            yield MirrorProgress::Applied(action);
+        }
+    }
+}
+
+async fn plan(trees: &[Tree; 2], options: &MirrorOptions, now: SystemTime) -> Result<MirrorPlan> {
+    let [src, dst] = trees;
+
+    if let ExtraneousPolicy::Archive(dir) = &options.extraneous {
+        if dir.trim_matches('/').is_empty() {
+            return Err(anyhow::anyhow!(
+                "The archive directory '{dir}' is not a directory in the destination"
+            )
+            .into());
+        }
+    }
+
+    let mut changed = HashSet::new();
+    for (path, s) in src {
+        let Some(d) = dst.get(path) else {
+            continue;
+        };
+        if s.file_type != FileType::File || d.file_type != FileType::File {
+            continue;
+        }
+
+        let differs = match options.compare {
+            MirrorCompare::SizeAndModified => {
+                s.size != d.size
+                    || matches!(
+                        (s.metadata.modified, d.metadata.modified),
+                        (Some(s), Some(d)) if s > d
+                    )
+            }
+            MirrorCompare::Checksum => !same_contents(s, d).await?,
+        };
+        if differs {
+            changed.insert(path.clone());
+        }
+    }
+
+    Ok(Planner::new(trees, &changed, &options.extraneous, now).plan())
+}
+
+struct Planner<'a> {
+    trees: &'a [Tree; 2],
+    changed: &'a HashSet<String>,
+    extraneous: &'a ExtraneousPolicy,
+    // the archive folder and the folder of this mirror in it
+    archive: Option<(&'a str, String)>,
+    plan: MirrorPlan,
+    // the directories the plan creates in the archive folder and for the files it copies
+    archive_dirs: Creates,
+    creates: Creates,
+    removals: Vec<MirrorAction>,
+    copies: Vec<MirrorAction>,
+    // the paths removed from the destination along with everything in them
+    removed: HashSet<String>,
+    // the paths left as they are because they are of another type in the destination
+    kept: HashSet<String>,
+}
+
+impl<'a> Planner<'a> {
+    fn new(
+        trees: &'a [Tree; 2],
+        changed: &'a HashSet<String>,
+        extraneous: &'a ExtraneousPolicy,
+        now: SystemTime,
+    ) -> Self {
+        // the folder is numbered if an earlier mirror in the same second has it already
+        let archive = match extraneous {
+            ExtraneousPolicy::Archive(dir) => {
+                let dir = dir.trim_matches('/');
+                let name = archive_name(now);
+                let folder = std::iter::once(name.clone())
+                    .chain((1..).map(|n| numbered_name(&name, n)))
+                    .map(|name| join(dir, &name))
+                    .find(|folder| !trees[1].contains_key(folder))
+                    .unwrap();
+                Some((dir, folder))
+            }
+            _ => None,
+        };
+
+        Self {
+            trees,
+            changed,
+            extraneous,
+            archive,
+            plan: MirrorPlan::default(),
+            archive_dirs: Creates::default(),
+            creates: Creates::default(),
+            removals: vec![],
+            copies: vec![],
+            removed: HashSet::new(),
+            kept: HashSet::new(),
+        }
+    }
+
+    fn plan(mut self) -> MirrorPlan {
+        let [src, dst] = self.trees;
+
+        // parents come before their children in both trees
+        for (path, d) in dst {
+            if self.in_archive(path) || self.is_removed(path) {
+                continue;
+            }
+
+            let s = src.get(path);
+            match s {
+                Some(s) if is_dir(s) == is_dir(d) => {}
+                _ if *self.extraneous == ExtraneousPolicy::Keep => {
+                    if s.is_some() {
+                        self.plan.skipped.push(path.clone());
+                        self.kept.insert(path.clone());
+                    }
+                }
+                _ => self.remove(path),
+            }
+        }
+
+        for (path, s) in src {
+            if self.in_archive(path) {
+                if !self.in_archive(split(path).0) {
+                    self.plan.skipped.push(path.clone());
+                }
+                continue;
+            }
+            if self.kept.contains(path) || ancestors(path).any(|p| self.kept.contains(p)) {
+                continue;
+            }
+
+            let exists = dst.contains_key(path) && !self.is_removed(path);
+            match is_dir(s) {
+                true => self.ensure_dir(path),
+                false if !exists || self.changed.contains(path) => self.copy(path),
+                false => {}
+            }
+        }
+
+        let create_dirs = |c: Creates| {
+            c.into_sorted()
+                .into_iter()
+                .map(|path| MirrorAction::CreateDir { path })
+        };
+
+        let mut plan = self.plan;
+        plan.actions = create_dirs(self.archive_dirs)
+            .chain(self.removals)
+            .chain(create_dirs(self.creates))
+            .chain(self.copies)
+            .collect();
+        plan
+    }
+
+    fn in_archive(&self, path: &str) -> bool {
+        self.archive
+            .as_ref()
+            .is_some_and(|(dir, _)| path == *dir || path.starts_with(&format!("{dir}/")))
+    }
+
+    fn is_removed(&self, path: &str) -> bool {
+        is_removed(&self.removed, path)
+    }
+
+    fn remove(&mut self, path: &str) {
+        self.removed.insert(path.to_owned());
+
+        let Some((_, folder)) = &self.archive else {
+            self.removals.push(MirrorAction::Delete {
+                path: path.to_owned(),
+            });
+            return;
+        };
+
+        let to = join(folder, path);
+        let (trees, removed) = (self.trees, &self.removed);
+        self.archive_dirs
+            .parents(&to, &|p| is_dir_in_dst(trees, removed, p));
+        self.removals.push(MirrorAction::Archive {
+            path: path.to_owned(),
+            to,
+        });
+    }
+
+    fn copy(&mut self, path: &str) {
+        let (trees, removed) = (self.trees, &self.removed);
+        self.creates
+            .parents(path, &|p| is_dir_in_dst(trees, removed, p));
+        self.copies.push(MirrorAction::Copy {
+            path: path.to_owned(),
+        });
+    }
+
+    fn ensure_dir(&mut self, path: &str) {
+        let (trees, removed) = (self.trees, &self.removed);
+        self.creates
+            .dir(path, &|p| is_dir_in_dst(trees, removed, p));
+    }
+}
+
+// Whether `path` or a directory it is in is in `removed`.
+fn is_removed(removed: &HashSet<String>, path: &str) -> bool {
+    removed.contains(path) || ancestors(path).any(|p| removed.contains(p))
+}
+
+// Whether the destination has a directory at `path` that the plan keeps.
+fn is_dir_in_dst(trees: &[Tree; 2], removed: &HashSet<String>, path: &str) -> bool {
+    trees[1].get(path).is_some_and(is_dir) && !is_removed(removed, path)
+}
+
+// Carries out the actions of a plan, keeping track of the files on both sides.
+struct Mirror<'a> {
+    roots: [&'a FileId; 2],
+    trees: [Tree; 2],
+}
+
+impl Mirror<'_> {
+    async fn apply(&mut self, action: &MirrorAction, options: &MirrorOptions) -> Result<()> {
+        match action {
+            MirrorAction::CreateDir { path } => {
+                let (parent, name) = split(path);
+                let dir = create(&FileType::Dir, name, &self.dir_id(parent)?).await?;
+                self.trees[1].insert(path.clone(), dir);
+            }
+            MirrorAction::Copy { path } => {
+                let src = self.file(0, path)?.id.clone();
+                let (parent, name) = split(path);
+                let dir = self.dir_id(parent)?;
+
+                if let Some(f) = copy_over(&src, name, &dir, &options.cancel).await? {
+                    self.trees[1].insert(path.clone(), f);
+                }
+            }
+            MirrorAction::Delete { path } => {
+                remove(self.file(1, path)?).await?;
+                forget(&mut self.trees[1], path);
+            }
+            MirrorAction::Archive { path, to } => {
+                let id = self.file(1, path)?.id.clone();
+                let dir = self.dir_id(split(to).0)?;
+
+                // never over a file archived before
+                let file = move_file(&id, &dir, MoveOptions::default()).await?;
+                forget(&mut self.trees[1], path);
+                self.trees[1].insert(to.clone(), file);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn file(&self, side: usize, path: &str) -> Result<&File> {
+        self.trees[side].get(path).ok_or_else(|| {
+            Error::not_found(format!("'{path}' is gone from '{}'", self.roots[side].1))
+        })
+    }
+
+    // The directory `path` in the destination.
+    fn dir_id(&self, path: &str) -> Result<FileId> {
+        match path.is_empty() {
+            true => Ok(self.roots[1].clone()),
+            false => Ok(self.file(1, path)?.id.clone()),
+        }
+    }
+}
+
+fn is_dir(f: &File) -> bool {
+    f.file_type == FileType::Dir
+}
+
+// The name of the archive folder of a mirror made at `time`, like `2024-05-01_12-30-00` in UTC.
+fn archive_name(time: SystemTime) -> String {
+    let secs = time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    let (days, rest) = (secs / 86400, secs % 86400);
+
+    // the civil date of a day count, after Howard Hinnant's `civil_from_days`
+    let z = days + 719468;
+    let era = z / 146097;
+    let doe = z % 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + u64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02}_{:02}-{:02}-{:02}",
+        rest / 3600,
+        rest % 3600 / 60,
+        rest % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{super::tree, *};
+
+    fn tree(side: &str, paths: &[(&str, u64)]) -> Tree {
+        paths
+            .iter()
+            .map(|&(path, size)| tree::tests::file(side, path, size, 0))
+            .collect()
+    }
+
+    #[test]
+    fn test_archive_name() {
+        let t = |secs| archive_name(UNIX_EPOCH + Duration::from_secs(secs));
+
+        assert_eq!(t(0), "1970-01-01_00-00-00");
+        assert_eq!(t(951_782_400 + 3661), "2000-02-29_01-01-01");
+        assert_eq!(t(1_714_566_600), "2024-05-01_12-30-00");
+    }
+
+    #[test]
+    fn test_plan() {
+        let trees = [
+            tree(
+                "src",
+                &[
+                    ("a", 1),
+                    ("b", 1),
+                    ("c/", 0),
+                    ("c/new", 1),
+                    ("clash", 1),
+                    ("n/", 0),
+                    ("n/m/", 0),
+                    ("n/m/z", 1),
+                ],
+            ),
+            tree(
+                "dst",
+                &[
+                    ("a", 1),
+                    ("b", 2),
+                    ("c/", 0),
+                    ("c/old", 1),
+                    ("clash/", 0),
+                    ("clash/x", 1),
+                    ("gone/", 0),
+                    ("gone/y", 1),
+                    (".archive/", 0),
+                ],
+            ),
+        ];
+        let changed = HashSet::from(["b".to_owned()]);
+        let now = UNIX_EPOCH + Duration::from_secs(1_714_566_600);
+        let plan = |policy: &ExtraneousPolicy| Planner::new(&trees, &changed, policy, now).plan();
+
+        use MirrorAction::*;
+        let s = |s: &str| s.to_owned();
+        let mirrored = [
+            CreateDir { path: s("n") },
+            CreateDir { path: s("n/m") },
+            Copy { path: s("b") },
+            Copy { path: s("c/new") },
+            Copy { path: s("clash") },
+            Copy { path: s("n/m/z") },
+        ];
+
+        let archived = plan(&ExtraneousPolicy::Archive(".archive".into()));
+        let folder = ".archive/2024-05-01_12-30-00";
+        let mut expected = vec![
+            CreateDir { path: s(folder) },
+            CreateDir {
+                path: format!("{folder}/c"),
+            },
+            Archive {
+                path: s("c/old"),
+                to: format!("{folder}/c/old"),
+            },
+            Archive {
+                path: s("clash"),
+                to: format!("{folder}/clash"),
+            },
+            Archive {
+                path: s("gone"),
+                to: format!("{folder}/gone"),
+            },
+        ];
+        expected.extend(mirrored.iter().cloned());
+        assert_eq!(archived.actions, expected);
+
+        let kept = plan(&ExtraneousPolicy::Keep);
+        let expected = mirrored
+            .iter()
+            .filter(|a| **a != Copy { path: s("clash") })
+            .cloned()
+            .collect::<Vec<_>>();
+        assert_eq!(kept.actions, expected);
+        assert_eq!(kept.skipped, ["clash"]);
+
+        // a mirror in the same second gets a folder of its own
+        let mut trees = trees.clone();
+        trees[1].extend([tree::tests::file("dst", &format!("{folder}/"), 0, 0)]);
+        let policy = ExtraneousPolicy::Archive(".archive".into());
+        let again = Planner::new(&trees, &changed, &policy, now).plan();
+        assert_eq!(
+            again.actions[0],
+            CreateDir {
+                path: format!("{folder} (1)")
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_plan_rejects_archive_root() {
+        let trees = [tree("src", &[("a", 1)]), tree("dst", &[("b", 1)])];
+
+        for dir in ["", "/"] {
+            let options = MirrorOptions {
+                extraneous: ExtraneousPolicy::Archive(dir.into()),
+                ..Default::default()
+            };
+            assert!(plan(&trees, &options, UNIX_EPOCH).await.is_err());
+        }
+    }
+}
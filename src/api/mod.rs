@@ -0,0 +1,345 @@
+mod conflict;
+mod copy;
+mod delete;
+mod du;
+mod mirror;
+mod mv;
+mod read;
+mod search;
+mod sync;
+mod trash;
+mod tree;
+mod walk;
+mod watch;
+
+pub use copy::*;
+pub use delete::*;
+pub use du::*;
+pub use mirror::*;
+pub use mv::*;
+pub use read::*;
+pub use search::*;
+pub use sync::*;
+pub use trash::*;
+pub use walk::*;
+pub use watch::*;
+
+use crate::*;
+use FileType as FT;
+
+use async_stream::{stream, try_stream};
+use conflict::{replace, resolve, Resolution};
+use futures::{Stream, StreamExt, TryStreamExt};
+
+/// Creates a file, failing with [`Error::AlreadyExists`] if `parent_id` already has a file named
+/// `name`.
+pub async fn create(file_type: &FT, name: &str, parent_id: &FileId) -> Result<File> {
+    create_with(file_type, name, parent_id, &ConflictPolicy::Fail).await
+}
+
+/// [`create`] with a policy for when `parent_id` already has a file named `name`.
+///
+/// If the policy leaves the existing file as it is, that file is returned instead.
+pub async fn create_with(
+    file_type: &FT,
+    name: &str,
+    parent_id: &FileId,
+    policy: &ConflictPolicy,
+) -> Result<File> {
+    match resolve(parent_id, name, None, policy).await? {
+        Resolution::Skip(existing) => Ok(*existing),
+        Resolution::Create(name) => create_unchecked(file_type, &name, parent_id).await,
+        Resolution::Replace { existing, temp } => {
+            let file = create_unchecked(file_type, &temp, parent_id).await?;
+            replace(parent_id, &existing, &file).await
+        }
+    }
+}
+
+// Creates a file without looking for one with the same name first, for directories that were
+// just created or names that were already resolved.
+pub(crate) async fn create_unchecked(
+    file_type: &FT,
+    name: &str,
+    parent_id: &FileId,
+) -> Result<File> {
+    let FileId(source, parent_id) = parent_id;
+    backend_for(source)
+        .await?
+        .create(file_type, name, parent_id)
+        .await
+}
+
+pub async fn get(file_id: &FileId) -> Result<File> {
+    let FileId(source, id) = file_id;
+    backend_for(source).await?.get(id).await
+}
+
+/// Creates a hard link named `name` in `dir_id` to the file `file_id`.
+pub async fn create_hard_link(file_id: &FileId, name: &str, dir_id: &FileId) -> Result<File> {
+    let (FileId(source, id), FileId(dir_source, dir_id)) = (file_id, dir_id);
+
+    if source != dir_source {
+        return Err(Error::unsupported(
+            "linking files across file sources is not supported",
+        ));
+    }
+
+    backend_for(source).await?.hard_link(id, name, dir_id).await
+}
+
+pub async fn rename(file_id: &FileId, new_name: &str) -> Result<()> {
+    let FileId(source, id) = file_id;
+    backend_for(source).await?.rename(id, new_name).await
+}
+
+/// Renames `file_id` with a policy for when its directory already has a file named `new_name`,
+/// returning the renamed file.
+///
+/// If the policy leaves the existing file as it is, the file keeps its name and is returned as it
+/// is.
+pub async fn rename_with(
+    file_id: &FileId,
+    new_name: &str,
+    policy: &ConflictPolicy,
+) -> Result<File> {
+    let FileId(source, id) = file_id;
+    let backend = backend_for(source).await?;
+    let file = backend.get(id).await?;
+
+    let Some(parent_id) = &file.parent_id else {
+        backend.rename(id, new_name).await?;
+        return backend.get(id).await;
+    };
+
+    let (name, existing) = match resolve(parent_id, new_name, Some(&file), policy).await? {
+        Resolution::Skip(_) => return Ok(file),
+        Resolution::Create(name) => (name, None),
+        Resolution::Replace { existing, temp } => (temp, Some(existing)),
+    };
+
+    backend.rename(id, &name).await?;
+
+    // local files are identified by their path, which the rename changed
+    let file = backend.find(&parent_id.1, &name).await?.ok_or_else(|| {
+        Error::not_found(format!("'{}' was renamed but could not be found", name))
+    })?;
+
+    match existing {
+        Some(existing) => replace(parent_id, &existing, &file).await,
+        None => Ok(file),
+    }
+}
+
+pub async fn delete_file(file_id: &FileId) -> Result<()> {
+    let FileId(source, id) = file_id;
+    backend_for(source).await?.delete_file(id).await
+}
+
+pub async fn delete_dir(dir_id: &FileId) -> Result<()> {
+    let FileId(source, id) = dir_id;
+    backend_for(source).await?.delete_dir(id).await
+}
+
+/// Moves `file_id` into `dir_id`, see [`move_with_progress`] for how moves across file sources
+/// work.
+pub async fn move_to_dir(file_id: &FileId, dir_id: &FileId) -> Result<()> {
+    move_file(file_id, dir_id, MoveOptions::default())
+        .await
+        .map(|_| ())
+}
+
+pub async fn mime(file_id: &FileId) -> Result<String> {
+    let FileId(source, id) = file_id;
+    backend_for(source).await?.mime(id).await
+}
+
+pub fn list(dir_id: &FileId) -> impl Stream<Item = Result<File>> + '_ {
+    list_with(dir_id, ListOptions::default())
+}
+
+/// Lists all files in `dir_id`, starting at [`ListOptions::cursor`] if there is one.
+///
+/// Sorted listings are fetched a page at a time, without a [`ListOptions::page_size`] that means
+/// all at once for local directories.
+pub fn list_with(dir_id: &FileId, options: ListOptions) -> impl Stream<Item = Result<File>> + '_ {
+    let FileId(source, id) = dir_id;
+
+    stream! {
+        let b = match backend_for(source).await {
+            Ok(b) => b,
+            Err(e) => {
+                yield Err(e);
+                return;
+            }
+        };
+
+        let paged = options.sort != SortKey::Unsorted
+            || options.page_size.is_some()
+            || options.cursor.is_some();
+        let mut entries = match paged {
+            true => pages(&*b, id, &options).boxed(),
+            false => b
+                .list(id, &options)
+                .try_filter(|f| futures::future::ready(options.include_hidden || !f.metadata.hidden))
+                .boxed(),
+        };
+
+        loop {
+            let next = tokio::select! {
+                biased;
+                _ = options.cancel.cancelled() => None,
+                v = entries.next() => Some(v),
+            };
+
+            match next {
+                Some(Some(v)) => yield v,
+                Some(None) => break,
+                None => {
+                    yield Err(Error::cancelled(format!("Listing '{id}' was cancelled")));
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// Lists a page of the files in `dir_id`, pass its [`Page::next`] in [`ListOptions::cursor`] to get
+/// the next one.
+///
+/// A page may have fewer files than [`ListOptions::page_size`] even if it is not the last, only a
+/// missing `next` means the listing is done.
+pub async fn list_page(dir_id: &FileId, options: &ListOptions) -> Result<Page> {
+    let FileId(source, id) = dir_id;
+    backend_for(source).await?.list_page(id, options).await
+}
+
+// The files of the pages of `dir_id` from `options.cursor` on.
+fn pages<'a>(
+    backend: &'a dyn Backend,
+    dir_id: &'a str,
+    options: &'a ListOptions,
+) -> impl Stream<Item = Result<File>> + 'a {
+    try_stream! {
+        let mut options = options.clone();
+
+        loop {
+            let page = backend.list_page(dir_id, &options).await?;
+            for f in page.files {
+                yield f;
+            }
+
+            match page.next {
+                Some(next) => options.cursor = Some(next),
+                None => break,
+            }
+        }
+    }
+}
+
+/// Opens `file_id` to stream its contents from the beginning, see [`read_range`] and
+/// [`read_seekable`] to read only parts of it.
+pub async fn read(file_id: &FileId) -> Result<BoxedAsyncRead<'static>> {
+    let FileId(source, id) = file_id;
+    backend_for(source).await?.read(id).await
+}
+
+/// Opens `file_id` to replace its contents with what is written, which is only complete once the
+/// writer was shut down.
+pub async fn write(file_id: &FileId) -> Result<BoxedAsyncWrite<'static>> {
+    let FileId(source, id) = file_id;
+    backend_for(source).await?.write(id).await
+}
+
+/// [`write`] with a mode to append to the file or write over only a part of it, see [`WriteMode`]
+/// for what that costs on Google Drive.
+pub async fn write_with(file_id: &FileId, mode: WriteMode) -> Result<BoxedAsyncWrite<'static>> {
+    let FileId(source, id) = file_id;
+    backend_for(source).await?.write_with(id, mode).await
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::AsyncWriteExt;
+
+    use super::*;
+    use crate::testing::TempDir;
+
+    async fn write_all(file_id: &FileId, mode: WriteMode, data: &str) -> Result<()> {
+        let mut w = write_with(file_id, mode).await?;
+        w.write_all(data.as_bytes()).await?;
+        w.shutdown().await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_write_with() {
+        let tmp = TempDir::new("write-with");
+        let id = tmp.id("a.txt");
+
+        write_all(&id, WriteMode::CreateNew, "abc").await.unwrap();
+        let e = write_all(&id, WriteMode::CreateNew, "x").await.unwrap_err();
+        assert!(matches!(e, Error::AlreadyExists { .. }), "{e}");
+        assert_eq!(tmp.read("a.txt").as_deref(), Some("abc"));
+
+        write_all(&id, WriteMode::Append, "def").await.unwrap();
+        assert_eq!(tmp.read("a.txt").as_deref(), Some("abcdef"));
+
+        write_all(&id, WriteMode::WriteAt(1), "XY").await.unwrap();
+        assert_eq!(tmp.read("a.txt").as_deref(), Some("aXYdef"));
+
+        // writing past the end leaves zeros in between
+        write_all(&id, WriteMode::WriteAt(8), "g").await.unwrap();
+        assert_eq!(tmp.read("a.txt").as_deref(), Some("aXYdef\0\0g"));
+
+        write_all(&id, WriteMode::Truncate, "new").await.unwrap();
+        assert_eq!(tmp.read("a.txt").as_deref(), Some("new"));
+    }
+
+    fn names(files: &[File]) -> Vec<&str> {
+        files.iter().map(|f| f.name.as_str()).collect()
+    }
+
+    #[tokio::test]
+    async fn test_list_pages() {
+        let tmp = TempDir::new("list-pages");
+        for name in ["file1", "file2", "file10", "File3", ".hidden"] {
+            tmp.write(name, name);
+        }
+        let dir = tmp.id("");
+
+        let mut options = ListOptions {
+            sort: SortKey::Name,
+            include_hidden: false,
+            page_size: Some(3),
+            ..Default::default()
+        };
+        let first = list_page(&dir, &options).await.unwrap();
+        assert_eq!(names(&first.files), ["file1", "file2", "File3"]);
+
+        options.cursor = first.next.clone();
+        let second = list_page(&dir, &options).await.unwrap();
+        assert_eq!(names(&second.files), ["file10"]);
+        assert!(second.next.is_none());
+
+        // a cursor kept as a string continues the same way
+        options.cursor = Some(Cursor::from(first.next.unwrap().to_string()));
+        let rest = list_with(&dir, options.clone())
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        assert_eq!(names(&rest), ["file10"]);
+
+        options.cursor = None;
+        options.direction = SortDirection::Descending;
+        options.include_hidden = true;
+        let all = list_with(&dir, options)
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        assert_eq!(
+            names(&all),
+            ["file10", "File3", "file2", "file1", ".hidden"]
+        );
+    }
+}
@@ -0,0 +1,310 @@
+use std::collections::HashMap;
+
+use async_stream::try_stream;
+use futures::{Stream, TryStreamExt};
+
+use super::{
+    conflict::{replace, resolve, Resolution},
+    copy::{checksum_algorithm, copy_tree, verify_copy, Tree},
+    create_unchecked,
+};
+use crate::*;
+
+/// Moves `file_id` into `dir_id`, reporting the progress of the move.
+///
+/// When both are on the same file source the file source moves the file itself, which for local
+/// files on different file systems means copying and deleting them. Otherwise, including between
+/// two Google Drive accounts, the file or directory is copied to `dir_id`, the copy is verified to
+/// have the same files and sizes as the source, and the same checksums where the file sources
+/// have any, and only then the source is deleted. If the verification fails the copy is deleted
+/// and the source is left untouched, as it is when the copy fails. Only regular files and
+/// directories of them can be moved that way, others fail with [`Error::Unsupported`].
+pub fn move_with_progress<'a>(
+    file_id: &'a FileId,
+    dir_id: &'a FileId,
+) -> impl Stream<Item = Result<MoveProgress>> + 'a {
+    move_with(file_id, dir_id, MoveOptions::default())
+}
+
+/// [`move_with_progress`] with a policy for when `dir_id` already has a file with the name of
+/// `file_id`.
+pub fn move_with<'a>(
+    file_id: &'a FileId,
+    dir_id: &'a FileId,
+    options: MoveOptions,
+) -> impl Stream<Item = Result<MoveProgress>> + 'a {
+    try_stream! {
+        let src = get(file_id).await?;
+
+        let (name, existing) = match resolve(dir_id, &src.name, Some(&src), &options.conflict).await? {
+            Resolution::Skip(existing) => {
+                yield MoveProgress::Skipped(*existing);
+                return;
+            }
+            Resolution::Create(name) => (name, None),
+            Resolution::Replace { existing, temp } => (temp, Some(existing)),
+        };
+        // the moved file takes the place of the existing one once it is complete
+        let swap = |f: File| async {
+            match &existing {
+                Some(existing) => replace(dir_id, existing, &f).await,
+                None => Ok(f),
+            }
+        };
+
+        if file_id.0 == dir_id.0 {
+            let backend = backend_for(&file_id.0).await?;
+
+            for await p in backend.mv_with_progress(&file_id.1, &dir_id.1, &name) {
+                match p? {
+                    MoveProgress::Done(f) => yield MoveProgress::Done(swap(f).await?),
+                    p => yield p,
+                }
+            }
+            return;
+        }
+
+        let mut copy = None;
+        for await p in copy_across(&src, &name, dir_id) {
+            match p? {
+                MoveProgress::Done(f) => copy = Some(f),
+                p => yield p,
+            }
+        }
+        let Some(copy) = copy else {
+            Err(anyhow::anyhow!("the copy of '{}' ended before it was done", src.id.1))?
+        };
+        let dest = swap(copy).await?;
+
+        yield MoveProgress::Deleting;
+        match src.file_type {
+            FileType::Dir => {
+                delete_recursive(&src.id, DeleteOptions::default())
+                    .try_for_each(|_| async { Ok(()) })
+                    .await?
+            }
+            _ => delete_file(&src.id).await?,
+        }
+
+        yield MoveProgress::Done(get(&dest.id).await?);
+    }
+}
+
+// Copies `src` to `name` in `dir_id` and verifies the copy, which is what `Done` has. The copy is
+// deleted again if anything fails before then.
+fn copy_across<'a>(
+    src: &'a File,
+    name: &'a str,
+    dir_id: &'a FileId,
+) -> impl Stream<Item = Result<MoveProgress>> + 'a {
+    try_stream! {
+        let options = CopyOptions::default();
+
+        match src.file_type {
+            FileType::Dir => {
+                let tree = Tree::scan(src.clone()).await?;
+                // deleting the source would delete what was not copied
+                if let Some(other) = tree.others.first() {
+                    Err(Error::unsupported(format!(
+                        "'{}' has '{}', which is not a regular file or directory, it can only be moved within its file source",
+                        src.name, other.name
+                    )))?;
+                }
+                let dest = create_unchecked(&FileType::Dir, name, dir_id).await?;
+
+                for await p in copy_tree(&tree, &dest.id, &options) {
+                    match p {
+                        Ok(p) => yield MoveProgress::Copying(p),
+                        Err(e) => {
+                            discard(&dest).await;
+                            Err(e)?;
+                        }
+                    }
+                }
+
+                yield MoveProgress::Verifying;
+                let matches = async {
+                    let copy = Tree::scan(get(&dest.id).await?).await?;
+                    same_tree(&tree, &copy).await
+                };
+                verify(&dest, matches.await).await?;
+
+                yield MoveProgress::Done(dest);
+            }
+            FileType::File => {
+                let dest = create_unchecked(&FileType::File, name, dir_id).await?;
+                let mut bytes_done = 0;
+
+                for await bytes in copy_into(&src.id, &dest.id, &options) {
+                    match bytes {
+                        Ok(bytes) => bytes_done += bytes,
+                        Err(e) => {
+                            discard(&dest).await;
+                            Err(e)?;
+                        }
+                    }
+                    yield MoveProgress::Copying(CopyDirProgress {
+                        current: src.clone(),
+                        files_done: 0,
+                        files_total: 1,
+                        bytes_done,
+                        bytes_total: src.size,
+                    });
+                }
+
+                yield MoveProgress::Verifying;
+                let copy = get(&dest.id).await;
+                let matches = match &copy {
+                    Ok(copy) => same_file(src, copy).await,
+                    Err(_) => Ok(false),
+                };
+                verify(&dest, matches).await?;
+
+                yield MoveProgress::Done(copy?);
+            }
+            // reading a FIFO or a device would block or never end
+            _ => Err(Error::unsupported(format!(
+                "'{}' is not a regular file or directory, it can only be moved within its file source",
+                src.name
+            )))?,
+        }
+    }
+}
+
+pub(crate) async fn move_file(
+    file_id: &FileId,
+    dir_id: &FileId,
+    options: MoveOptions,
+) -> Result<File> {
+    let s = move_with(file_id, dir_id, options);
+    futures::pin_mut!(s);
+
+    while let Some(p) = s.try_next().await? {
+        if let MoveProgress::Done(f) | MoveProgress::Skipped(f) = p {
+            return Ok(f);
+        }
+    }
+
+    Err(anyhow::anyhow!("the move of '{}' ended before it was done", file_id.1).into())
+}
+
+// Deletes the unverified copy `dest` unless it matches its source, keeping the error of checking
+// that.
+async fn verify(dest: &File, matches: Result<bool>) -> Result<()> {
+    match matches {
+        Ok(true) => Ok(()),
+        Ok(false) => {
+            discard(dest).await;
+            Err(Error::VerificationFailed {
+                message: format!("The copy of '{}' does not match its source", dest.name),
+                source: None,
+            })
+        }
+        Err(e) => {
+            discard(dest).await;
+            Err(e)
+        }
+    }
+}
+
+// Deletes the unfinished or unverified copy `dest`.
+async fn discard(dest: &File) {
+    // the copy is useless either way, so failing to delete it is not worth reporting over the
+    // error that made it so
+    let _ = match dest.file_type {
+        FileType::Dir => {
+            delete_recursive(&dest.id, DeleteOptions::default())
+                .try_for_each(|_| async { Ok(()) })
+                .await
+        }
+        _ => delete_file(&dest.id).await,
+    };
+}
+
+// Whether `copy` has the files of `src` at the same paths, compared by `same_file`.
+async fn same_tree(src: &Tree, copy: &Tree) -> Result<bool> {
+    let copies = copy.file_paths().collect::<HashMap<_, _>>();
+    if src.dirs.len() != copy.dirs.len() || src.files.len() != copies.len() {
+        return Ok(false);
+    }
+
+    for (path, f) in src.file_paths() {
+        match copies.get(&path) {
+            Some(c) if same_file(f, c).await? => {}
+            _ => return Ok(false),
+        }
+    }
+
+    Ok(true)
+}
+
+// Whether `copy` has the size of `src` and, if either file source has a checksum of them, the
+// same checksum.
+async fn same_file(src: &File, copy: &File) -> Result<bool> {
+    if src.size != copy.size {
+        return Ok(false);
+    }
+
+    match verify_copy(src, copy, checksum_algorithm(src, copy)).await {
+        Ok(()) | Err(Error::Unsupported { .. }) => Ok(true),
+        Err(Error::ChecksumMismatch { .. }) => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    async fn run(src: &FileId, dir: &FileId) -> Result<Vec<MoveProgress>> {
+        let src = get(src).await?;
+        copy_across(&src, &src.name, dir).try_collect().await
+    }
+
+    #[tokio::test]
+    async fn test_copy_across() {
+        let tmp = TempDir::new("mv");
+        tmp.write("src/d/a.txt", "a");
+        tmp.write("src/d/e/b.txt", "bb");
+        tmp.write("src/c.txt", "c");
+        std::fs::create_dir(tmp.path("dest")).unwrap();
+
+        let events = run(&tmp.id("src/d"), &tmp.id("dest")).await.unwrap();
+        assert!(matches!(events.last(), Some(MoveProgress::Done(f)) if f.id == tmp.id("dest/d")));
+        assert_eq!(tmp.read("dest/d/a.txt").as_deref(), Some("a"));
+        assert_eq!(tmp.read("dest/d/e/b.txt").as_deref(), Some("bb"));
+
+        run(&tmp.id("src/c.txt"), &tmp.id("dest")).await.unwrap();
+        assert_eq!(tmp.read("dest/c.txt").as_deref(), Some("c"));
+    }
+
+    #[tokio::test]
+    async fn test_copy_across_rejects_links() {
+        let tmp = TempDir::new("mv-links");
+        tmp.write("src/d/a.txt", "a");
+        std::os::unix::fs::symlink("a.txt", tmp.path("src/d/link")).unwrap();
+        std::fs::create_dir(tmp.path("dest")).unwrap();
+
+        let e = run(&tmp.id("src/d"), &tmp.id("dest")).await.unwrap_err();
+        assert!(matches!(e, Error::Unsupported { .. }), "{e}");
+        assert!(!tmp.path("dest/d").exists());
+        assert!(tmp.path("src/d/link").is_symlink());
+    }
+
+    #[tokio::test]
+    async fn test_copy_across_cleans_up() {
+        let tmp = TempDir::new("mv-failed");
+        tmp.write("src/a.txt", "a");
+        std::fs::create_dir(tmp.path("dest")).unwrap();
+
+        // the source is gone by the time it is copied
+        let src = get(&tmp.id("src/a.txt")).await.unwrap();
+        std::fs::remove_file(tmp.path("src/a.txt")).unwrap();
+        let copied = copy_across(&src, &src.name, &tmp.id("dest"))
+            .try_collect::<Vec<_>>()
+            .await;
+        assert!(copied.is_err());
+        assert!(!tmp.path("dest/a.txt").exists());
+    }
+}
@@ -0,0 +1,147 @@
+use std::{
+    io,
+    pin::Pin,
+    sync::Arc,
+    task::{ready, Context, Poll},
+};
+
+use futures::{future::BoxFuture, FutureExt};
+use tokio::io::{AsyncRead, AsyncSeek, ReadBuf};
+
+use crate::*;
+
+/// Reads at most `len` bytes of `file_id` starting at `offset`.
+///
+/// Google Drive only sends the requested range. File sources that cannot read ranges read the file
+/// from its beginning and skip everything before `offset`.
+pub async fn read_range(
+    file_id: &FileId,
+    offset: u64,
+    len: u64,
+) -> Result<BoxedAsyncRead<'static>> {
+    let FileId(source, id) = file_id;
+    let backend = backend_for(source).await?;
+
+    if len == 0 {
+        // fail like a non-empty read would for a missing file
+        backend.get(id).await?;
+        return Ok(Box::pin(tokio::io::empty()));
+    }
+
+    backend.read_range(id, offset, len).await
+}
+
+/// Opens `file_id` for reading at any offset, like a local file.
+///
+/// Local files are read directly. Google Drive files are read with a new range request after every
+/// seek that moves away from the current offset, so reads close to each other should not be
+/// separated by seeks.
+pub async fn read_seekable(file_id: &FileId) -> Result<BoxedAsyncSeekRead<'static>> {
+    let FileId(source, id) = file_id;
+    let backend = backend_for(source).await?;
+
+    match backend.read_seekable(id).await {
+        Err(Error::Unsupported { .. }) => {}
+        r => return r,
+    }
+
+    let size = backend.get(id).await?.size;
+
+    Ok(Box::pin(RangeReader {
+        backend,
+        id: id.clone(),
+        size,
+        pos: 0,
+        state: State::Idle,
+    }))
+}
+
+// A seekable reader that reads the rest of the file from its offset with `Backend::read_range`
+// whenever a seek moved it.
+struct RangeReader {
+    backend: Arc<dyn Backend>,
+    id: String,
+    size: u64,
+    pos: u64,
+    state: State,
+}
+
+enum State {
+    Idle,
+    Opening(BoxFuture<'static, Result<BoxedAsyncRead<'static>>>),
+    Reading(BoxedAsyncRead<'static>),
+}
+
+impl AsyncRead for RangeReader {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+
+        loop {
+            match &mut this.state {
+                State::Idle => {
+                    if this.pos >= this.size {
+                        return Poll::Ready(Ok(()));
+                    }
+
+                    let backend = this.backend.clone();
+                    let id = this.id.clone();
+                    let (offset, len) = (this.pos, this.size - this.pos);
+
+                    this.state = State::Opening(
+                        async move { backend.read_range(&id, offset, len).await }.boxed(),
+                    );
+                }
+                State::Opening(open) => {
+                    let reader = ready!(open.as_mut().poll(cx));
+                    match reader {
+                        Ok(reader) => this.state = State::Reading(reader),
+                        Err(e) => {
+                            this.state = State::Idle;
+                            return Poll::Ready(Err(io::Error::other(e)));
+                        }
+                    }
+                }
+                State::Reading(reader) => {
+                    let filled = buf.filled().len();
+                    ready!(reader.as_mut().poll_read(cx, buf))?;
+                    this.pos += (buf.filled().len() - filled) as u64;
+
+                    return Poll::Ready(Ok(()));
+                }
+            }
+        }
+    }
+}
+
+impl AsyncSeek for RangeReader {
+    fn start_seek(self: Pin<&mut Self>, position: io::SeekFrom) -> io::Result<()> {
+        let this = self.get_mut();
+
+        let pos = match position {
+            io::SeekFrom::Start(pos) => Some(pos),
+            io::SeekFrom::End(delta) => this.size.checked_add_signed(delta),
+            io::SeekFrom::Current(delta) => this.pos.checked_add_signed(delta),
+        };
+        let pos = pos.ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )
+        })?;
+
+        if pos != this.pos {
+            this.state = State::Idle;
+            this.pos = pos;
+        }
+
+        Ok(())
+    }
+
+    fn poll_complete(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<u64>> {
+        Poll::Ready(Ok(self.pos))
+    }
+}
@@ -0,0 +1,63 @@
+use async_stream::stream;
+use futures::{Stream, StreamExt};
+
+use crate::*;
+
+/// Finds the files matching `query` in `root` and all directories below it.
+///
+/// Google Drive searches on the server, looking through a few folders with every request. Local
+/// directories are walked and every entry is checked, reading the start of files to tell their
+/// mime type if the query has one.
+pub fn search(root: &FileId, query: Query) -> impl Stream<Item = Result<File>> + '_ {
+    stream! {
+        let backend = match backend_for(&root.0).await {
+            Ok(b) => b,
+            Err(e) => {
+                yield Err(e);
+                return;
+            }
+        };
+
+        let mut found = backend.search(&root.1, &query);
+        let mut first = true;
+        let mut unsupported = false;
+
+        while let Some(f) = found.next().await {
+            match f {
+                Err(Error::Unsupported { .. }) if first => {
+                    unsupported = true;
+                    break;
+                }
+                f => yield f,
+            }
+            first = false;
+        }
+
+        // the file source cannot search, so the files are checked here
+        if !unsupported {
+            return;
+        }
+
+        for await e in walk(root, WalkOptions::default()) {
+            let file = match e {
+                Ok(e) if query.matches_metadata(&e.file) => e.file,
+                Ok(_) => continue,
+                Err(e) => {
+                    yield Err(e);
+                    continue;
+                }
+            };
+
+            let Some(mime_type) = &query.mime_type else {
+                yield Ok(file);
+                continue;
+            };
+
+            match backend.mime(&file.id.1).await {
+                Ok(m) if m == *mime_type => yield Ok(file),
+                Ok(_) => {}
+                Err(e) => yield Err(e),
+            }
+        }
+    }
+}
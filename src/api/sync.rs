@@ -0,0 +1,870 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use async_stream::try_stream;
+use futures::{Stream, TryStreamExt};
+
+use super::{
+    conflict::numbered_name,
+    tree::{ancestors, below, copy_over, depth, join, same_contents, scan, split, Creates, Tree},
+};
+use crate::*;
+
+const SIDES: [Side; 2] = [Side::Left, Side::Right];
+
+/// Plans what [`sync`] would do to bring `left` and `right` in sync, without changing anything.
+pub async fn sync_plan(
+    left: &FileId,
+    right: &FileId,
+    state: &SyncState,
+    options: &SyncOptions,
+) -> Result<SyncPlan> {
+    state.check(left, right)?;
+    let trees = [scan(left).await?, scan(right).await?];
+
+    plan(state, &trees, options.conflict).await
+}
+
+/// Brings the directories `left` and `right` in sync, on any pair of file sources.
+///
+/// What changed on either side since the last sync is found by comparing both directories with
+/// `state`, which the sync then updates with what it did. Created and modified files are copied to
+/// the other side, deleted ones are deleted there and renamed ones renamed. A file modified on one
+/// side and deleted on the other is copied back. Files changed on both sides are only conflicts if
+/// their contents differ, which [`SyncOptions::conflict`] resolves.
+///
+/// Every action first checks that what it changes is still as the plan found it, and is
+/// [skipped](SyncProgress::Skipped) otherwise. Deleting a directory only deletes the files that
+/// were in it when the plan was made, and keeps the directory if anything else is left in it.
+///
+/// The first event is the [`SyncPlan`], followed by every action once it was carried out.
+pub fn sync<'a>(
+    left: &'a FileId,
+    right: &'a FileId,
+    state: &'a mut SyncState,
+    options: SyncOptions,
+) -> impl Stream<Item = Result<SyncProgress>> + 'a {
+    try_stream! {
+        state.check(left, right)?;
+        let trees = [scan(left).await?, scan(right).await?];
+        let plan = plan(state, &trees, options.conflict).await?;
+
+        yield SyncProgress::Planned(plan.clone());
+
+        let mut syncer = Syncer::new([left, right], trees, &plan);
+
+        for action in plan.actions {
+            let applied = match options.cancel.is_cancelled() {
+                true => Err(Error::cancelled(format!("Syncing '{}' was cancelled", left.1))),
+                false => syncer.apply(&action, &options).await,
+            };
+
+            match applied {
+                Ok(true) => {
+                    syncer.done(&action);
+                    yield SyncProgress::Applied(action);
+                }
+                Ok(false) => yield SyncProgress::Skipped(action),
+                Err(e) => {
+                    // the error is what gets reported, the actions carried out so far are kept
+                    *state = syncer.state(state);
+                    Err(e)?;
+                }
+            }
+        }
+
+        *state = syncer.state(state);
+    }
+}
+
+async fn plan(
+    state: &SyncState,
+    trees: &[Tree; 2],
+    policy: SyncConflictPolicy,
+) -> Result<SyncPlan> {
+    let changes = Changes { state, trees };
+
+    // files changed the same way on both sides are no conflict
+    let mut same = HashSet::new();
+    for (path, l) in trees[0].iter() {
+        let Some(r) = trees[1].get(path) else {
+            continue;
+        };
+        if l.file_type == FileType::File
+            && r.file_type == FileType::File
+            && SIDES.iter().all(|&s| changes.get(s, path).is_changed())
+            && same_contents(l, r).await?
+        {
+            same.insert(path.clone());
+        }
+    }
+
+    Ok(Planner::new(changes, &same, policy).plan())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Change {
+    // neither there now nor at the last sync
+    Absent,
+    Unchanged,
+    Created,
+    Modified,
+    Deleted,
+}
+
+impl Change {
+    fn is_changed(self) -> bool {
+        matches!(self, Self::Created | Self::Modified)
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Changes<'a> {
+    state: &'a SyncState,
+    trees: &'a [Tree; 2],
+}
+
+impl Changes<'_> {
+    fn get(&self, side: Side, path: &str) -> Change {
+        let i = side.index();
+
+        match (
+            self.state.entries.get(path).map(|e| &e[i]),
+            self.trees[i].get(path),
+        ) {
+            (Some(s), Some(f)) if s.matches(f) => Change::Unchanged,
+            (Some(_), Some(_)) => Change::Modified,
+            (Some(_), None) => Change::Deleted,
+            (None, Some(_)) => Change::Created,
+            (None, None) => Change::Absent,
+        }
+    }
+
+    fn file(&self, side: Side, path: &str) -> Option<&File> {
+        self.trees[side.index()].get(path)
+    }
+
+    fn is_dir(&self, side: Side, path: &str) -> bool {
+        self.file(side, path)
+            .is_some_and(|f| f.file_type == FileType::Dir)
+    }
+
+    // Whether anything in the directory `path` on `side` was created or modified.
+    fn changed_below(&self, side: Side, path: &str) -> bool {
+        let prefix = format!("{path}/");
+
+        self.trees[side.index()]
+            .range(prefix.clone()..)
+            .take_while(|(p, _)| p.starts_with(&prefix))
+            .any(|(p, _)| self.get(side, p).is_changed())
+    }
+}
+
+struct Planner<'a> {
+    changes: Changes<'a>,
+    same: &'a HashSet<String>,
+    policy: SyncConflictPolicy,
+    plan: SyncPlan,
+    renames: Vec<SyncAction>,
+    copies: Vec<SyncAction>,
+    deletes: Vec<(Side, String)>,
+    // the directories the plan creates and the paths it puts files at, by side
+    creates: [Creates; 2],
+    // the paths dealt with by renames
+    renamed: HashSet<String>,
+}
+
+impl<'a> Planner<'a> {
+    fn new(changes: Changes<'a>, same: &'a HashSet<String>, policy: SyncConflictPolicy) -> Self {
+        Self {
+            changes,
+            same,
+            policy,
+            plan: SyncPlan::default(),
+            renames: vec![],
+            copies: vec![],
+            deletes: vec![],
+            creates: Default::default(),
+            renamed: HashSet::new(),
+        }
+    }
+
+    fn plan(mut self) -> SyncPlan {
+        for side in SIDES {
+            self.renames(side);
+        }
+
+        let c = self.changes;
+        let paths = c
+            .state
+            .entries
+            .keys()
+            .chain(c.trees[0].keys())
+            .chain(c.trees[1].keys())
+            .collect::<BTreeSet<_>>();
+
+        for path in paths {
+            if !self.renamed.contains(path) {
+                self.path(path);
+            }
+        }
+
+        // deleting a directory deletes what is in it
+        let deleted = self.deletes.iter().cloned().collect::<HashSet<_>>();
+        self.deletes.retain(|(side, path)| {
+            !ancestors(path).any(|p| deleted.contains(&(*side, p.to_owned())))
+        });
+
+        // parents are created before their children and deleted after them
+        self.deletes
+            .sort_by_key(|(_, path)| std::cmp::Reverse((depth(path), path.clone())));
+
+        let mut plan = self.plan;
+        let [left, right] = self.creates;
+        let creates = [(Side::Left, left), (Side::Right, right)]
+            .into_iter()
+            .flat_map(|(side, c)| {
+                c.into_sorted()
+                    .into_iter()
+                    .map(move |path| SyncAction::CreateDir { side, path })
+            });
+        let deletes = self
+            .deletes
+            .into_iter()
+            .map(|(side, path)| SyncAction::Delete { side, path });
+
+        plan.actions = creates
+            .chain(self.renames)
+            .chain(self.copies)
+            .chain(deletes)
+            .collect();
+        plan
+    }
+
+    // Renames the files renamed on `side` on the other side as well.
+    //
+    // A renamed file is a deleted one whose id or size and modification time match those of a
+    // created one. Local files are identified by their paths, so only the latter finds them.
+    fn renames(&mut self, side: Side) {
+        let (c, i, other) = (self.changes, side.index(), side.other());
+
+        let deleted = c
+            .state
+            .entries
+            .iter()
+            .filter(|(p, e)| !e[i].is_dir && c.get(side, p) == Change::Deleted)
+            .collect::<Vec<_>>();
+        let created = c.trees[i]
+            .iter()
+            .filter(|(p, f)| f.file_type == FileType::File && c.get(side, p) == Change::Created)
+            .collect::<Vec<_>>();
+
+        let same_file = |s: &Stamp, f: &File| {
+            s.id == f.id.1
+                || (s.size == f.size && s.modified.is_some() && s.modified == f.metadata.modified)
+        };
+
+        for (from, entry) in deleted.iter() {
+            let mut matches = created.iter().filter(|(_, f)| same_file(&entry[i], f));
+            let (Some((to, file)), None) = (matches.next(), matches.next()) else {
+                continue;
+            };
+
+            let ambiguous = deleted
+                .iter()
+                .filter(|(_, e)| same_file(&e[i], file))
+                .count()
+                > 1;
+            if ambiguous
+                || self.renamed.contains(*to)
+                || c.get(other, from) != Change::Unchanged
+                || c.get(other, to) != Change::Absent
+            {
+                continue;
+            }
+
+            self.creates[other.index()].parents(to, &|p| c.is_dir(other, p));
+            self.renames.push(SyncAction::Rename {
+                side: other,
+                from: (*from).clone(),
+                to: (*to).clone(),
+            });
+            self.renamed.insert((*from).clone());
+            self.renamed.insert((*to).clone());
+
+            // the file may have been changed along with its name
+            let mut stamp = Stamp::from(*file);
+            stamp.id.clone_from(&entry[i].id);
+            if stamp != entry[i] {
+                self.copy(side, to);
+            }
+        }
+    }
+
+    fn path(&mut self, path: &str) {
+        let c = self.changes;
+
+        let files = [c.file(Side::Left, path), c.file(Side::Right, path)];
+        let dirs = [c.is_dir(Side::Left, path), c.is_dir(Side::Right, path)];
+
+        if files[0].is_some() && files[1].is_some() && dirs[0] != dirs[1] {
+            self.plan.skipped.push(path.to_owned());
+            return;
+        }
+
+        let was_dir = c.state.entries.get(path).is_some_and(|e| e[0].is_dir);
+        if dirs[0] || dirs[1] || (files.iter().all(Option::is_none) && was_dir) {
+            self.dir(path);
+            return;
+        }
+
+        use Change::*;
+        match (c.get(Side::Left, path), c.get(Side::Right, path)) {
+            (Created | Modified, Created | Modified) if !self.same.contains(path) => {
+                self.conflict(path)
+            }
+            (Created | Modified, Unchanged | Absent | Deleted) => self.copy(Side::Left, path),
+            (Unchanged | Absent | Deleted, Created | Modified) => self.copy(Side::Right, path),
+            (Deleted, Unchanged) => self.delete(Side::Right, path),
+            (Unchanged, Deleted) => self.delete(Side::Left, path),
+            _ => {}
+        }
+    }
+
+    fn dir(&mut self, path: &str) {
+        let c = self.changes;
+
+        for side in SIDES {
+            let other = side.other();
+            if c.file(side, path).is_none() || c.file(other, path).is_some() {
+                continue;
+            }
+
+            // a directory deleted on one side is only deleted on the other if nothing in it
+            // changed there, otherwise it is created again
+            if c.get(other, path) == Change::Deleted
+                && c.get(side, path) == Change::Unchanged
+                && !c.changed_below(side, path)
+            {
+                self.delete(side, path);
+            } else {
+                self.creates[other.index()].dir(path, &|p| c.is_dir(other, p));
+            }
+        }
+    }
+
+    fn conflict(&mut self, path: &str) {
+        self.plan.conflicts.push(path.to_owned());
+
+        match self.policy {
+            SyncConflictPolicy::Prefer(side) => self.copy(side, path),
+            SyncConflictPolicy::NewerWins => {
+                let modified = |side| {
+                    self.changes
+                        .file(side, path)
+                        .and_then(|f| f.metadata.modified)
+                };
+                match modified(Side::Right) > modified(Side::Left) {
+                    true => self.copy(Side::Right, path),
+                    false => self.copy(Side::Left, path),
+                }
+            }
+            SyncConflictPolicy::KeepBoth => {
+                let (parent, name) = split(path);
+                let taken = |p: &String| {
+                    SIDES.iter().any(|&s| {
+                        self.changes.file(s, p).is_some() || self.creates[s.index()].is_taken(p)
+                    })
+                };
+                let free = (1..)
+                    .map(|n| join(parent, &numbered_name(name, n)))
+                    .find(|p| !taken(p))
+                    .unwrap();
+
+                self.renames.push(SyncAction::Rename {
+                    side: Side::Right,
+                    from: path.to_owned(),
+                    to: free.clone(),
+                });
+                self.creates[Side::Right.index()].take(&free);
+                self.copy(Side::Right, &free);
+                self.copy(Side::Left, path);
+            }
+        }
+    }
+
+    fn copy(&mut self, from: Side, path: &str) {
+        let (c, to) = (self.changes, from.other());
+
+        let creates = &mut self.creates[to.index()];
+        creates.parents(path, &|p| c.is_dir(to, p));
+        creates.take(path);
+        self.copies.push(SyncAction::Copy {
+            from,
+            path: path.to_owned(),
+        });
+    }
+
+    fn delete(&mut self, side: Side, path: &str) {
+        self.deletes.push((side, path.to_owned()));
+    }
+}
+
+// Carries out the actions of a plan, keeping track of the files on both sides.
+struct Syncer<'a> {
+    roots: [&'a FileId; 2],
+    trees: [Tree; 2],
+    // the number of actions not carried out yet for every path, which keep their last state
+    pending: HashMap<String, usize>,
+}
+
+impl<'a> Syncer<'a> {
+    fn new(roots: [&'a FileId; 2], trees: [Tree; 2], plan: &SyncPlan) -> Self {
+        let mut syncer = Self {
+            roots,
+            trees,
+            pending: HashMap::new(),
+        };
+
+        for a in &plan.actions {
+            for path in syncer.paths(a) {
+                *syncer.pending.entry(path).or_default() += 1;
+            }
+        }
+
+        syncer
+    }
+
+    // The paths `action` changes.
+    fn paths(&self, action: &SyncAction) -> Vec<String> {
+        match action {
+            SyncAction::CreateDir { path, .. } | SyncAction::Copy { path, .. } => {
+                vec![path.clone()]
+            }
+            SyncAction::Rename { from, to, .. } => vec![from.clone(), to.clone()],
+            SyncAction::Delete { side, path } => {
+                let mut paths = vec![path.clone()];
+                paths.extend(below(&self.trees[side.index()], path).map(|(p, _)| p.clone()));
+                paths
+            }
+        }
+    }
+
+    fn done(&mut self, action: &SyncAction) {
+        for path in self.paths_done(action) {
+            if let Some(n) = self.pending.get_mut(&path) {
+                *n -= 1;
+            }
+        }
+    }
+
+    // `paths` of an action that was carried out, whose deleted entries are gone from the trees.
+    fn paths_done(&self, action: &SyncAction) -> Vec<String> {
+        match action {
+            SyncAction::Delete { path, .. } => self
+                .pending
+                .keys()
+                .filter(|p| *p == path || p.starts_with(&format!("{path}/")))
+                .cloned()
+                .collect(),
+            a => self.paths(a),
+        }
+    }
+
+    // Carries out `action`, unless what it changes is no longer what the scan found, or an action
+    // it needs was left out. Returns whether it was carried out.
+    async fn apply(&mut self, action: &SyncAction, options: &SyncOptions) -> Result<bool> {
+        match action {
+            SyncAction::CreateDir { side, path } => {
+                let (parent, name) = split(path);
+                let Some(dir) = self.dir_id(*side, parent) else {
+                    return Ok(false);
+                };
+                if !self.unchanged(*side, path).await? {
+                    return Ok(false);
+                }
+
+                let created = create(&FileType::Dir, name, &dir).await?;
+                self.trees[side.index()].insert(path.clone(), created);
+            }
+            SyncAction::Rename { side, from, to } => {
+                let (parent, name) = split(to);
+                let (Some(file), Some(dir)) = (self.file(*side, from), self.dir_id(*side, parent))
+                else {
+                    return Ok(false);
+                };
+                let file = file.clone();
+                if !self.unchanged(*side, from).await? || !self.unchanged(*side, to).await? {
+                    return Ok(false);
+                }
+
+                let file = match file.parent_id.as_ref() == Some(&dir) {
+                    true => rename_with(&file.id, name, &ConflictPolicy::Fail).await?,
+                    false => {
+                        backend_for(&dir.0)
+                            .await?
+                            .mv(&file.id.1, &dir.1, name)
+                            .await?
+                    }
+                };
+
+                let tree = &mut self.trees[side.index()];
+                tree.remove(from);
+                tree.insert(to.clone(), file);
+            }
+            SyncAction::Copy { from, path } => {
+                let to = from.other();
+                let (parent, name) = split(path);
+                let (Some(src), Some(dir)) = (self.file(*from, path), self.dir_id(to, parent))
+                else {
+                    return Ok(false);
+                };
+                let src = src.id.clone();
+                if !self.unchanged(to, path).await? {
+                    return Ok(false);
+                }
+
+                if let Some(f) = copy_over(&src, name, &dir, &options.cancel).await? {
+                    self.trees[to.index()].insert(path.clone(), f);
+                }
+            }
+            SyncAction::Delete { side, path } => return self.delete(*side, path).await,
+        }
+
+        Ok(true)
+    }
+
+    // Deletes `path` on `side` with the entries the scan found in it, deepest first. Entries that
+    // changed since are kept, and so are the directories they are in.
+    async fn delete(&mut self, side: Side, path: &str) -> Result<bool> {
+        let i = side.index();
+        let mut paths = vec![path.to_owned()];
+        paths.extend(below(&self.trees[i], path).map(|(p, _)| p.clone()));
+
+        let mut kept = false;
+        for p in paths.iter().rev() {
+            if !self.unchanged(side, p).await? {
+                kept = true;
+                continue;
+            }
+
+            let file = &self.trees[i][p];
+            let FileId(source, id) = &file.id;
+            let backend = backend_for(source).await?;
+
+            match file.file_type {
+                FileType::Dir => {
+                    let options = ListOptions::default();
+                    let mut entries = backend.list(id, &options);
+                    if entries.try_next().await?.is_some() {
+                        kept = true;
+                        continue;
+                    }
+                    drop(entries);
+                    backend.delete_dir(id).await?;
+                }
+                _ => backend.delete_file(id).await?,
+            }
+            self.trees[i].remove(p);
+        }
+
+        Ok(!kept)
+    }
+
+    // Whether what is at `path` on `side` now is what the scan found there, or what the actions
+    // carried out so far put there.
+    async fn unchanged(&self, side: Side, path: &str) -> Result<bool> {
+        let (parent, name) = split(path);
+        let Some(FileId(source, dir)) = self.dir_id(side, parent) else {
+            return Ok(false);
+        };
+        let found = backend_for(&source).await?.find(&dir, name).await?;
+
+        Ok(match (self.file(side, path), found) {
+            (None, None) => true,
+            (Some(known), Some(found)) => Stamp::from(known).matches(&found),
+            _ => false,
+        })
+    }
+
+    fn file(&self, side: Side, path: &str) -> Option<&File> {
+        self.trees[side.index()].get(path)
+    }
+
+    fn dir_id(&self, side: Side, path: &str) -> Option<FileId> {
+        match path.is_empty() {
+            true => Some(self.roots[side.index()].clone()),
+            false => self.file(side, path).map(|f| f.id.clone()),
+        }
+    }
+
+    // The state after the actions carried out so far. Paths with actions left keep their last
+    // state, so the next sync picks them up again.
+    fn state(&self, last: &SyncState) -> SyncState {
+        let mut state = SyncState::new(&last.left, &last.right);
+        let paths = last
+            .entries
+            .keys()
+            .chain(self.trees[0].keys())
+            .chain(self.trees[1].keys())
+            .collect::<BTreeSet<_>>();
+
+        for path in paths {
+            if self.pending.get(path).is_some_and(|&n| n > 0) {
+                if let Some(e) = last.entries.get(path) {
+                    state.entries.insert(path.clone(), e.clone());
+                }
+                continue;
+            }
+
+            if let (Some(l), Some(r)) = (self.trees[0].get(path), self.trees[1].get(path)) {
+                if l.file_type == r.file_type {
+                    state
+                        .entries
+                        .insert(path.clone(), [Stamp::from(l), Stamp::from(r)]);
+                }
+            }
+        }
+
+        state
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::{super::tree, *};
+
+    fn file(side: Side, path: &str, size: u64, modified: u64) -> (String, File) {
+        tree::tests::file(&side.to_string(), path, size, modified)
+    }
+
+    #[test]
+    fn test_plan() {
+        let root = FileId(FileSource::Local, "/".into());
+        let synced = [
+            ("a.txt", 1),
+            ("b.txt", 1),
+            ("c.txt", 1),
+            ("d/", 1),
+            ("d/x", 1),
+            ("e/", 1),
+            ("e/y", 1),
+            ("f.txt", 1),
+            ("g.txt", 9),
+        ];
+
+        let mut state = SyncState::new(&root, &root);
+        for (path, n) in synced {
+            let stamps = SIDES.map(|s| Stamp::from(&file(s, path, n, n).1));
+            state.entries.insert(file(Side::Left, path, n, n).0, stamps);
+        }
+
+        let left = [
+            file(Side::Left, "a.txt", 2, 2),    // modified
+            file(Side::Left, "b.txt", 1, 1),    // deleted on the right
+            file(Side::Left, "e/", 0, 1),       // deleted on the right with its file
+            file(Side::Left, "e/y", 1, 1),      //
+            file(Side::Left, "f.txt", 3, 3),    // modified on both sides
+            file(Side::Left, "h.txt", 9, 9),    // renamed from g.txt
+            file(Side::Left, "n/", 0, 1),       // created with a file
+            file(Side::Left, "n/z", 2, 7),      //
+            file(Side::Left, "same.txt", 5, 5), // created the same on both sides
+        ];
+        let right = [
+            file(Side::Right, "a.txt", 1, 1),
+            file(Side::Right, "c.txt", 1, 1), // deleted on the left
+            file(Side::Right, "d/", 0, 1),    // deleted on the left, but a file was added
+            file(Side::Right, "d/x", 1, 1),
+            file(Side::Right, "d/new", 1, 1),
+            file(Side::Right, "f.txt", 4, 4),
+            file(Side::Right, "g.txt", 9, 9),
+            file(Side::Right, "same.txt", 5, 6),
+        ];
+
+        let trees = [left.into_iter().collect(), right.into_iter().collect()];
+        let changes = Changes {
+            state: &state,
+            trees: &trees,
+        };
+        let same = HashSet::from(["same.txt".to_owned()]);
+        let plan = Planner::new(changes, &same, SyncConflictPolicy::KeepBoth).plan();
+
+        use Side::*;
+        use SyncAction::*;
+        let s = |s: &str| s.to_owned();
+        assert_eq!(
+            plan.actions,
+            [
+                CreateDir {
+                    side: Left,
+                    path: s("d")
+                },
+                CreateDir {
+                    side: Right,
+                    path: s("n")
+                },
+                Rename {
+                    side: Right,
+                    from: s("g.txt"),
+                    to: s("h.txt")
+                },
+                Rename {
+                    side: Right,
+                    from: s("f.txt"),
+                    to: s("f (1).txt")
+                },
+                Copy {
+                    from: Left,
+                    path: s("a.txt")
+                },
+                Copy {
+                    from: Right,
+                    path: s("d/new")
+                },
+                Copy {
+                    from: Right,
+                    path: s("f (1).txt")
+                },
+                Copy {
+                    from: Left,
+                    path: s("f.txt")
+                },
+                Copy {
+                    from: Left,
+                    path: s("n/z")
+                },
+                Delete {
+                    side: Right,
+                    path: s("d/x")
+                },
+                Delete {
+                    side: Left,
+                    path: s("e")
+                },
+                Delete {
+                    side: Right,
+                    path: s("c.txt")
+                },
+                Delete {
+                    side: Left,
+                    path: s("b.txt")
+                },
+            ]
+        );
+        assert_eq!(plan.conflicts, ["f.txt"]);
+    }
+
+    // Two empty directories to sync, which are removed again with `remove_dirs`.
+    fn temp_dirs(name: &str) -> [FileId; 2] {
+        let root = std::env::temp_dir().join(format!("files-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+
+        SIDES.map(|side| {
+            let dir = root.join(side.to_string());
+            std::fs::create_dir_all(&dir).unwrap();
+            FileId(FileSource::Local, dir.to_string_lossy().to_string())
+        })
+    }
+
+    fn remove_dirs(dirs: &[FileId; 2]) {
+        let root = std::path::Path::new(&dirs[0].1).parent().unwrap();
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    fn write(dir: &FileId, path: &str, contents: &str) {
+        let path = std::path::Path::new(&dir.1).join(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, contents).unwrap();
+    }
+
+    fn read(dir: &FileId, path: &str) -> Option<String> {
+        std::fs::read_to_string(std::path::Path::new(&dir.1).join(path)).ok()
+    }
+
+    async fn run(dirs: &[FileId; 2], state: &mut SyncState) -> Vec<SyncProgress> {
+        sync(&dirs[0], &dirs[1], state, SyncOptions::default())
+            .try_collect()
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_sync() {
+        let dirs = temp_dirs("sync");
+        let [left, right] = &dirs;
+        // renames are found by size and modification time, which the files must not share
+        write(left, "a.txt", "a");
+        write(left, "d/x", "xx");
+        write(right, "b.txt", "b");
+
+        let mut state = SyncState::new(left, right);
+        run(&dirs, &mut state).await;
+        assert_eq!(read(right, "a.txt").as_deref(), Some("a"));
+        assert_eq!(read(right, "d/x").as_deref(), Some("xx"));
+        assert_eq!(read(left, "b.txt").as_deref(), Some("b"));
+        assert_eq!(state.entries.len(), 4);
+
+        // what changed since is found through the state
+        std::fs::remove_file(std::path::Path::new(&left.1).join("a.txt")).unwrap();
+        let d = std::path::Path::new(&left.1).join("d");
+        std::fs::rename(d.join("x"), d.join("y")).unwrap();
+        write(right, "b.txt", "changed");
+
+        let events = run(&dirs, &mut state).await;
+        assert!(events.iter().any(|e| matches!(
+            e,
+            SyncProgress::Applied(SyncAction::Rename {
+                side: Side::Right,
+                ..
+            })
+        )));
+        assert_eq!(read(right, "a.txt"), None);
+        assert_eq!(read(right, "d/x"), None);
+        assert_eq!(read(right, "d/y").as_deref(), Some("xx"));
+        assert_eq!(read(left, "b.txt").as_deref(), Some("changed"));
+        assert_eq!(run(&dirs, &mut state).await.len(), 1);
+
+        remove_dirs(&dirs);
+    }
+
+    #[tokio::test]
+    async fn test_apply_skips_changed_targets() {
+        let dirs = temp_dirs("sync-changed");
+        let [left, right] = &dirs;
+        write(left, "d/x", "x");
+        write(left, "e.txt", "e");
+
+        let mut last = SyncState::new(left, right);
+        run(&dirs, &mut last).await;
+
+        std::fs::remove_dir_all(std::path::Path::new(&left.1).join("d")).unwrap();
+        write(left, "e.txt", "changed");
+
+        let trees = [scan(left).await.unwrap(), scan(right).await.unwrap()];
+        let plan = plan(&last, &trees, SyncConflictPolicy::KeepBoth)
+            .await
+            .unwrap();
+        let mut syncer = Syncer::new([left, right], trees, &plan);
+
+        // the right side changes after the plan was made
+        write(right, "d/new", "new");
+        write(right, "e.txt", "other");
+
+        let options = SyncOptions::default();
+        for action in &plan.actions {
+            assert!(!syncer.apply(action, &options).await.unwrap(), "{action}");
+        }
+        assert_eq!(read(right, "d/x"), None);
+        assert_eq!(read(right, "d/new").as_deref(), Some("new"));
+        assert_eq!(read(right, "e.txt").as_deref(), Some("other"));
+
+        // the skipped paths keep their last state, so the next sync looks at them again
+        let state = syncer.state(&last);
+        for path in ["d", "d/x", "e.txt"] {
+            assert_eq!(state.entries.get(path), last.entries.get(path), "{path}");
+        }
+
+        remove_dirs(&dirs);
+    }
+}
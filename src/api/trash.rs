@@ -0,0 +1,47 @@
+use async_stream::stream;
+use futures::{Stream, StreamExt};
+
+use crate::*;
+
+/// Moves `file_id` to the trash of its file source, from where it can be restored with [`restore`].
+///
+/// Local files go to the FreeDesktop.org trash shared with desktop file managers: the trash in the
+/// home directory for files on the same file system, the `.Trash-$uid` directory at the top of
+/// other file systems. Trashing fails with [`Error::Unsupported`] on file systems where that
+/// directory cannot be created, like read-only mounts.
+pub async fn trash(file_id: &FileId) -> Result<TrashedFile> {
+    let FileId(source, id) = file_id;
+    backend_for(source).await?.trash(id).await
+}
+
+/// Lists the files in the trash of `source`, on Google Drive only those that were trashed
+/// themselves, not the files in trashed folders.
+pub fn list_trash(source: &FileSource) -> impl Stream<Item = Result<TrashedFile>> + '_ {
+    stream! {
+        let backend = match backend_for(source).await {
+            Ok(b) => b,
+            Err(e) => {
+                yield Err(e);
+                return;
+            }
+        };
+
+        let mut files = backend.list_trash();
+        while let Some(f) = files.next().await {
+            yield f;
+        }
+    }
+}
+
+/// Moves the trashed file `trashed_id`, the id of a [`TrashedFile::file`], back to where it was.
+///
+/// Restoring a local file fails with [`Error::AlreadyExists`] if its original path is taken.
+pub async fn restore(trashed_id: &FileId) -> Result<File> {
+    let FileId(source, id) = trashed_id;
+    backend_for(source).await?.restore(id).await
+}
+
+/// Deletes everything in the trash of `source` for good.
+pub async fn empty_trash(source: &FileSource) -> Result<()> {
+    backend_for(source).await?.empty_trash().await
+}
@@ -0,0 +1,193 @@
+// What sync and mirror share: scanning the two directories, comparing and copying their files,
+// and planning the directories to create.
+
+use std::collections::{BTreeMap, HashSet};
+
+use futures::{future, StreamExt, TryStreamExt};
+use tokio_util::sync::CancellationToken;
+
+use super::copy::{checksum_algorithm, file_checksum};
+use crate::*;
+
+// The files and directories in a directory that is synced or mirrored, by their paths relative
+// to it with `/` between names.
+pub(super) type Tree = BTreeMap<String, File>;
+
+// Everything in `root`. A directory that cannot be listed fails the scan, its files would look
+// deleted otherwise.
+pub(super) async fn scan(root: &FileId) -> Result<Tree> {
+    walk(root, WalkOptions::default())
+        .try_filter(|e| future::ready(matches!(e.file.file_type, FileType::File | FileType::Dir)))
+        .map_ok(|e| (e.path, e.file))
+        .try_collect()
+        .await
+}
+
+// Whether `l` and `r` have the same contents, by the checksums the file sources keep or
+// otherwise by hashing them.
+pub(super) async fn same_contents(l: &File, r: &File) -> Result<bool> {
+    if l.size != r.size {
+        return Ok(false);
+    }
+
+    let algorithm = checksum_algorithm(l, r);
+
+    let (l, r) = future::try_join(file_checksum(l, algorithm), file_checksum(r, algorithm)).await?;
+    Ok(match (l, r) {
+        (Some(l), Some(r)) => l.eq_ignore_ascii_case(&r),
+        _ => false,
+    })
+}
+
+// The directories a plan creates in a tree, each after the one it is in.
+#[derive(Default)]
+pub(super) struct Creates {
+    // the directories to create, and the other paths the plan puts files at
+    taken: HashSet<String>,
+    dirs: Vec<String>,
+}
+
+impl Creates {
+    // Creates the directory `path` and the ones it is in, unless `is_dir` says they are there.
+    pub fn dir(&mut self, path: &str, is_dir: &impl Fn(&str) -> bool) {
+        self.parents(path, is_dir);
+
+        if !is_dir(path) && self.taken.insert(path.to_owned()) {
+            self.dirs.push(path.to_owned());
+        }
+    }
+
+    // Creates the directories `path` is in.
+    pub fn parents(&mut self, path: &str, is_dir: &impl Fn(&str) -> bool) {
+        let (parent, _) = split(path);
+        if !parent.is_empty() {
+            self.dir(parent, is_dir);
+        }
+    }
+
+    // Marks `path` as taken by a file the plan puts there.
+    pub fn take(&mut self, path: &str) {
+        self.taken.insert(path.to_owned());
+    }
+
+    pub fn is_taken(&self, path: &str) -> bool {
+        self.taken.contains(path)
+    }
+
+    // The directories to create, parents before their children.
+    pub fn into_sorted(mut self) -> Vec<String> {
+        self.dirs.sort_by_key(|p| (depth(p), p.clone()));
+        self.dirs
+    }
+}
+
+// Copies `src` into `dir` as `name`, replacing the file there, and returns the copy.
+pub(super) async fn copy_over(
+    src: &FileId,
+    name: &str,
+    dir: &FileId,
+    cancel: &CancellationToken,
+) -> Result<Option<File>> {
+    let options = CopyOptions {
+        conflict: ConflictPolicy::Overwrite,
+        cancel: cancel.clone(),
+        ..Default::default()
+    };
+    let copy = copy_to_dir_with(src, name, dir, options);
+    futures::pin_mut!(copy);
+
+    let mut copied = None;
+    while let Some(p) = copy.next().await {
+        match p {
+            CopyProgress::Completed(s) => copied = Some(s.file),
+            CopyProgress::Skipped(f) => copied = Some(f),
+            CopyProgress::Failed(e) => return Err(e),
+            _ => {}
+        }
+    }
+
+    Ok(copied)
+}
+
+// Deletes `file`, or the directory with everything in it.
+pub(super) async fn remove(file: &File) -> Result<()> {
+    match file.file_type {
+        FileType::Dir => {
+            delete_recursive(&file.id, DeleteOptions::default())
+                .try_for_each(|_| async { Ok(()) })
+                .await
+        }
+        _ => delete_file(&file.id).await,
+    }
+}
+
+// Removes `path` and everything in it from `tree`.
+pub(super) fn forget(tree: &mut Tree, path: &str) {
+    let gone = below(tree, path)
+        .map(|(p, _)| p.clone())
+        .collect::<Vec<_>>();
+    for p in gone.iter().map(String::as_str).chain([path]) {
+        tree.remove(p);
+    }
+}
+
+// The entries in the directory `path` of `tree`, at any depth.
+pub(super) fn below<'t>(
+    tree: &'t Tree,
+    path: &str,
+) -> impl Iterator<Item = (&'t String, &'t File)> {
+    let prefix = format!("{path}/");
+
+    tree.range(prefix.clone()..)
+        .take_while(move |(p, _)| p.starts_with(&prefix))
+}
+
+pub(super) fn ancestors(path: &str) -> impl Iterator<Item = &str> {
+    path.match_indices('/').map(move |(i, _)| &path[..i])
+}
+
+pub(super) fn depth(path: &str) -> usize {
+    path.matches('/').count()
+}
+
+// The parent and the name of `path`, the parent of top level entries is empty.
+pub(super) fn split(path: &str) -> (&str, &str) {
+    path.rsplit_once('/').unwrap_or(("", path))
+}
+
+pub(super) fn join(parent: &str, name: &str) -> String {
+    match parent.is_empty() {
+        true => name.to_owned(),
+        false => format!("{parent}/{name}"),
+    }
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use super::*;
+
+    // The entry at `path` in the directory `/{root}`, a directory if `path` ends with `/`.
+    pub fn file(root: &str, path: &str, size: u64, modified: u64) -> (String, File) {
+        let is_dir = path.ends_with('/');
+        let path = path.trim_end_matches('/');
+
+        let file = File {
+            name: split(path).1.to_owned(),
+            file_type: if is_dir {
+                FileType::Dir
+            } else {
+                FileType::File
+            },
+            size,
+            id: FileId(FileSource::Local, format!("/{root}/{path}")),
+            parent_id: None,
+            metadata: Metadata {
+                modified: Some(UNIX_EPOCH + Duration::from_secs(modified)),
+                ..Default::default()
+            },
+        };
+        (path.to_owned(), file)
+    }
+}
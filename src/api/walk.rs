@@ -0,0 +1,353 @@
+use std::{
+    collections::HashSet,
+    sync::{Arc, Mutex},
+};
+
+use async_stream::stream;
+use futures::{
+    stream::{self, BoxStream},
+    Stream, StreamExt, TryStreamExt,
+};
+use glob::{MatchOptions, Pattern};
+
+use crate::*;
+
+/// Yields everything in the directory `dir_id` and, recursively, in its subdirectories.
+///
+/// Up to [`WalkOptions::concurrency`] directories are listed at the same time, which speeds up
+/// walking Google Drive folders, where every listing is a request. A directory that cannot be
+/// listed is reported as an error and the walk carries on with the rest.
+pub fn walk(dir_id: &FileId, options: WalkOptions) -> impl Stream<Item = Result<WalkEntry>> + '_ {
+    stream! {
+        let walker = match Walker::new(dir_id, options).await {
+            Ok(w) => w,
+            Err(e) => {
+                yield Err(e);
+                return;
+            }
+        };
+        let walker = &walker;
+
+        if walker.options.follow_links {
+            walker.first_visit(&dir_id.1).await;
+        }
+
+        let root = Dir {
+            id: dir_id.1.clone(),
+            path: String::new(),
+            depth: 0,
+        };
+
+        match walker.options.order {
+            WalkOrder::BreadthFirst => {
+                let mut level = vec![root];
+
+                while !level.is_empty() {
+                    let mut next = vec![];
+                    let mut listings = stream::iter(level)
+                        .map(|d| async move { walker.children(&d).await })
+                        .buffered(walker.concurrency());
+
+                    while let Some(children) = listings.next().await {
+                        let children = match children {
+                            Ok(c) => c,
+                            Err(e) => {
+                                yield Err(e);
+                                continue;
+                            }
+                        };
+
+                        for c in children {
+                            if c.descend {
+                                next.push(Dir::from(&c.entry));
+                            }
+                            if c.show {
+                                yield Ok(c.entry);
+                            }
+                        }
+                    }
+
+                    level = next;
+                }
+            }
+            WalkOrder::DepthFirst => match walker.children(&root).await {
+                Ok(children) => {
+                    for await e in walker.depth_first(children) {
+                        yield e;
+                    }
+                }
+                Err(e) => yield Err(e),
+            },
+        }
+    }
+}
+
+struct Walker {
+    backend: Arc<dyn Backend>,
+    source: FileSource,
+    options: WalkOptions,
+    include: Vec<Pattern>,
+    exclude: Vec<Pattern>,
+    // the directories walked so far, only kept when following links
+    visited: Mutex<HashSet<String>>,
+}
+
+// A directory to list.
+struct Dir {
+    id: String,
+    path: String,
+    depth: usize,
+}
+
+impl From<&WalkEntry> for Dir {
+    fn from(e: &WalkEntry) -> Self {
+        Self {
+            id: e.file.id.1.clone(),
+            path: e.path.clone(),
+            depth: e.depth,
+        }
+    }
+}
+
+struct Child {
+    entry: WalkEntry,
+    // whether the entry is yielded
+    show: bool,
+    // whether the entry is a directory to walk into
+    descend: bool,
+}
+
+impl Walker {
+    async fn new(dir_id: &FileId, options: WalkOptions) -> Result<Self> {
+        let patterns = |globs: &[String]| {
+            globs
+                .iter()
+                .map(|g| {
+                    Pattern::new(g)
+                        .map_err(|e| anyhow::anyhow!("Invalid glob pattern '{}': {}", g, e).into())
+                })
+                .collect::<Result<Vec<_>>>()
+        };
+
+        Ok(Self {
+            backend: backend_for(&dir_id.0).await?,
+            source: dir_id.0.clone(),
+            include: patterns(&options.include)?,
+            exclude: patterns(&options.exclude)?,
+            options,
+            visited: Mutex::new(HashSet::new()),
+        })
+    }
+
+    fn concurrency(&self) -> usize {
+        self.options.concurrency.max(1)
+    }
+
+    // Lists `dir`, leaving out excluded entries.
+    async fn children(&self, dir: &Dir) -> Result<Vec<Child>> {
+        let list_options = ListOptions {
+            follow_links: self.options.follow_links,
+            ..Default::default()
+        };
+        let files = self
+            .backend
+            .list(&dir.id, &list_options)
+            .try_collect::<Vec<_>>()
+            .await?;
+
+        let depth = dir.depth + 1;
+        let mut children = Vec::with_capacity(files.len());
+
+        for file in files {
+            let path = match dir.path.is_empty() {
+                true => file.name.clone(),
+                false => format!("{}/{}", dir.path, file.name),
+            };
+
+            if matches_any(&self.exclude, &file.name, &path) {
+                continue;
+            }
+
+            let show = self.include.is_empty() || matches_any(&self.include, &file.name, &path);
+            let entry = WalkEntry { file, path, depth };
+
+            let descend = entry.file.file_type == FileType::Dir
+                && self.options.max_depth.is_none_or(|max| depth < max)
+                && !self
+                    .options
+                    .prune
+                    .as_ref()
+                    .is_some_and(|prune| prune(&entry))
+                && (!self.options.follow_links || self.first_visit(&entry.file.id.1).await);
+
+            children.push(Child {
+                entry,
+                show,
+                descend,
+            });
+        }
+
+        Ok(children)
+    }
+
+    // Yields `children` and what is in them, each directory followed by its entries.
+    fn depth_first(&self, children: Vec<Child>) -> BoxStream<'_, Result<WalkEntry>> {
+        stream! {
+            let dirs = children
+                .iter()
+                .filter(|c| c.descend)
+                .map(|c| Dir::from(&c.entry))
+                .collect::<Vec<_>>();
+            let mut listings = stream::iter(dirs)
+                .map(|d| async move { self.children(&d).await })
+                .buffered(self.concurrency());
+
+            for c in children {
+                if c.show {
+                    yield Ok(c.entry);
+                }
+                if !c.descend {
+                    continue;
+                }
+
+                match listings.next().await {
+                    Some(Ok(grandchildren)) => {
+                        for await e in self.depth_first(grandchildren) {
+                            yield e;
+                        }
+                    }
+                    Some(Err(e)) => yield Err(e),
+                    None => unreachable!("every directory to walk into is listed"),
+                }
+            }
+        }
+        .boxed()
+    }
+
+    // Records the directory `id` as walked, returning whether it was not walked before.
+    //
+    // Local directories reached through links have other paths than their targets, so they are
+    // told apart by their real paths.
+    async fn first_visit(&self, id: &str) -> bool {
+        let key = match self.source {
+            FileSource::Local => tokio::fs::canonicalize(id)
+                .await
+                .map(|p| p.to_string_lossy().to_string())
+                .unwrap_or_else(|_| id.to_owned()),
+            _ => id.to_owned(),
+        };
+
+        self.visited.lock().unwrap().insert(key)
+    }
+}
+
+fn matches_any(patterns: &[Pattern], name: &str, path: &str) -> bool {
+    let options = MatchOptions {
+        require_literal_separator: true,
+        ..Default::default()
+    };
+
+    patterns.iter().any(|p| match p.as_str().contains('/') {
+        true => p.matches_with(path, options),
+        false => p.matches_with(name, options),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    async fn paths(tmp: &TempDir, options: WalkOptions) -> Vec<String> {
+        let root = tmp.id("");
+        let mut paths = walk(&root, options)
+            .map_ok(|e| e.path)
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        paths.sort();
+        paths
+    }
+
+    fn tree(name: &str) -> TempDir {
+        let tmp = TempDir::new(name);
+        tmp.write("a/x.rs", "x");
+        tmp.write("a/b/y.rs", "y");
+        tmp.write("c/z.txt", "z");
+        tmp.write("d.txt", "d");
+        tmp
+    }
+
+    #[tokio::test]
+    async fn test_walk_filters() {
+        let tmp = tree("walk-filters");
+
+        let all = paths(&tmp, WalkOptions::default()).await;
+        assert_eq!(
+            all,
+            ["a", "a/b", "a/b/y.rs", "a/x.rs", "c", "c/z.txt", "d.txt"]
+        );
+
+        let options = WalkOptions {
+            max_depth: Some(2),
+            ..Default::default()
+        };
+        let shallow = paths(&tmp, options).await;
+        assert_eq!(shallow, ["a", "a/b", "a/x.rs", "c", "c/z.txt", "d.txt"]);
+
+        let options = WalkOptions {
+            include: vec!["*.rs".into()],
+            exclude: vec!["b".into()],
+            ..Default::default()
+        };
+        assert_eq!(paths(&tmp, options).await, ["a/x.rs"]);
+
+        let options = WalkOptions {
+            include: vec!["a/*".into()],
+            prune: Some(Arc::new(|e: &WalkEntry| e.path == "c")),
+            ..Default::default()
+        };
+        assert_eq!(paths(&tmp, options).await, ["a/b", "a/x.rs"]);
+    }
+
+    #[tokio::test]
+    async fn test_walk_order() {
+        let tmp = tree("walk-order");
+        let root = tmp.id("");
+
+        let options = WalkOptions {
+            order: WalkOrder::BreadthFirst,
+            ..Default::default()
+        };
+        let entries = walk(&root, options).try_collect::<Vec<_>>().await.unwrap();
+        assert!(entries.is_sorted_by_key(|e| e.depth));
+
+        // every directory is followed by everything in it
+        let options = WalkOptions {
+            order: WalkOrder::DepthFirst,
+            ..Default::default()
+        };
+        let entries = walk(&root, options).try_collect::<Vec<_>>().await.unwrap();
+        for (i, e) in entries.iter().enumerate() {
+            let inside = |r: &WalkEntry| r.path.starts_with(&format!("{}/", e.path));
+            let count = entries.iter().filter(|r| inside(r)).count();
+            assert!(entries[i + 1..][..count].iter().all(inside), "{}", e.path);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_walk_links() {
+        let tmp = tree("walk-links");
+        std::os::unix::fs::symlink("..", tmp.path("a/up")).unwrap();
+
+        let options = WalkOptions {
+            follow_links: true,
+            ..Default::default()
+        };
+        let all = paths(&tmp, options).await;
+        assert_eq!(
+            all,
+            ["a", "a/b", "a/b/y.rs", "a/up", "a/x.rs", "c", "c/z.txt", "d.txt"]
+        );
+    }
+}
@@ -0,0 +1,48 @@
+use async_stream::stream;
+use futures::{Stream, StreamExt};
+
+use crate::*;
+
+/// Reports what changes in the directory `dir_id`, and with [`WatchOptions::recursive`] in its
+/// subdirectories, until the stream is dropped or [`WatchOptions::cancel`] is cancelled.
+///
+/// Local directories are watched with inotify, on Linux only. Every subdirectory of a recursive
+/// watch takes one of the inotify watches the system allows per user.
+///
+/// Google Drive is asked for its changes every [`WatchOptions::poll_interval`]. The watched
+/// folder is listed when the watch starts, to tell the changes in it from the changes anywhere
+/// else. Changes made while nothing was watching are only reported with a
+/// [`WatchOptions::cursor_file`], which keeps what was in the folder along with the page token, so
+/// the folder is only listed by the first watch using it. Network errors and rate limits are
+/// retried a few times before they end the watch.
+pub fn watch(
+    dir_id: &FileId,
+    options: WatchOptions,
+) -> impl Stream<Item = Result<WatchEvent>> + '_ {
+    let FileId(source, id) = dir_id;
+
+    stream! {
+        let b = match backend_for(source).await {
+            Ok(b) => b,
+            Err(e) => {
+                yield Err(e);
+                return;
+            }
+        };
+
+        let mut events = b.watch(id, &options);
+
+        loop {
+            let next = tokio::select! {
+                biased;
+                _ = options.cancel.cancelled() => None,
+                v = events.next() => v,
+            };
+
+            match next {
+                Some(v) => yield v,
+                None => break,
+            }
+        }
+    }
+}
@@ -0,0 +1,309 @@
+use std::{collections::HashMap, sync::Arc};
+
+use async_trait::async_trait;
+use futures::{
+    stream::{self, BoxStream},
+    StreamExt, TryStreamExt,
+};
+use tokio::{io::AsyncReadExt, sync::RwLock};
+
+use crate::*;
+
+lazy_static::lazy_static! {
+    static ref BACKENDS: RwLock<HashMap<String, Arc<dyn Backend>>> = RwLock::new(HashMap::new());
+}
+
+/// A storage backend that files can live on.
+///
+/// Every method receives ids that are local to the backend, i.e. the second field of a [`FileId`].
+/// The [`File`]s returned by a backend are expected to carry the [`FileSource`] the backend is
+/// reachable under, e.g. `FileSource::Custom(name)` for a backend registered with
+/// [`register_backend`].
+#[async_trait]
+pub trait Backend: Send + Sync {
+    async fn get(&self, id: &str) -> Result<File>;
+
+    /// Lists the files in `dir_id` in any order, only using [`ListOptions::follow_links`].
+    fn list<'a>(&'a self, dir_id: &'a str, options: &'a ListOptions)
+        -> BoxStream<'a, Result<File>>;
+
+    /// Lists a page of the files in `dir_id` as described by `options`.
+    ///
+    /// Defaults to listing and sorting the whole directory with [`list`](Backend::list) for the
+    /// first page, and keeping the sorted files for the following pages for a few minutes. Their
+    /// cursors hold the offset of the page in the sorted files, so a page whose listing is gone
+    /// lists the directory again.
+    async fn list_page(&self, dir_id: &str, options: &ListOptions) -> Result<Page> {
+        let (token, offset) = match &options.cursor {
+            None => (None, 0),
+            Some(c) => c
+                .as_str()
+                .split_once('.')
+                .and_then(|(t, o)| Some((Some(t.parse::<u64>().ok()?), o.parse::<usize>().ok()?)))
+                .ok_or_else(|| anyhow::anyhow!("Invalid cursor '{}'", c))?,
+        };
+
+        let kept = token.and_then(|t| Some((sort::kept_listing(t)?, Some(t))));
+        let (files, token) = match kept {
+            Some(kept) => kept,
+            None => {
+                let files = self
+                    .list(dir_id, options)
+                    .try_filter(|f| {
+                        futures::future::ready(options.include_hidden || !f.metadata.hidden)
+                    })
+                    .try_collect::<Vec<_>>()
+                    .await;
+                let mut files = files?;
+                sort::sort(&mut files, options.sort, options.direction);
+                (Arc::new(files), None)
+            }
+        };
+
+        let end = options
+            .page_size
+            .map_or(files.len(), |n| offset.saturating_add(n).min(files.len()));
+        let next = match end < files.len() {
+            true => {
+                let token = token.unwrap_or_else(|| sort::keep_listing(files.clone()));
+                Some(Cursor::from(format!("{token}.{end}")))
+            }
+            false => {
+                if let Some(token) = token {
+                    sort::drop_listing(token);
+                }
+                None
+            }
+        };
+        let files = files[offset.min(end)..end].to_vec();
+
+        Ok(Page { files, next })
+    }
+
+    /// The file named `name` in `dir_id`, if there is one.
+    ///
+    /// Defaults to listing `dir_id` until the file turns up.
+    async fn find(&self, dir_id: &str, name: &str) -> Result<Option<File>> {
+        let options = ListOptions::default();
+
+        let found = self
+            .list(dir_id, &options)
+            .try_filter(|f| futures::future::ready(f.name == name))
+            .try_next()
+            .await;
+        found
+    }
+
+    /// Finds the files matching `query` in `root_id` and all directories below it.
+    ///
+    /// Searches fall back to walking `root_id` and checking every file if this returns
+    /// [`Error::Unsupported`], which is the default.
+    fn search<'a>(&'a self, _root_id: &'a str, _query: &'a Query) -> BoxStream<'a, Result<File>> {
+        stream::once(async {
+            Err(Error::unsupported(
+                "searching is not supported by this file source",
+            ))
+        })
+        .boxed()
+    }
+
+    async fn read(&self, id: &str) -> Result<BoxedAsyncRead<'static>>;
+
+    /// Reads at most `len` bytes of the file `id` starting at `offset`, `len` is never `0`.
+    ///
+    /// Defaults to reading the file from its beginning and skipping everything before `offset`.
+    async fn read_range(&self, id: &str, offset: u64, len: u64) -> Result<BoxedAsyncRead<'static>> {
+        let mut reader = self.read(id).await?;
+
+        tokio::io::copy(&mut (&mut reader).take(offset), &mut tokio::io::sink())
+            .await
+            .with_context(|| format!("Could not read file '{}'", id))?;
+
+        Ok(Box::pin(reader.take(len)))
+    }
+
+    /// Opens the file `id` for reading at any offset.
+    ///
+    /// Seekable reads fall back to a reader built on [`read_range`](Backend::read_range) if this
+    /// returns [`Error::Unsupported`], which is the default.
+    async fn read_seekable(&self, _id: &str) -> Result<BoxedAsyncSeekRead<'static>> {
+        Err(Error::unsupported(
+            "seekable reads are not supported by this file source",
+        ))
+    }
+
+    async fn write(&self, id: &str) -> Result<BoxedAsyncWrite<'static>>;
+
+    /// Opens the file `id` for writing with `mode`.
+    ///
+    /// Defaults to [`write`](Backend::write) for [`WriteMode::Truncate`] and fails with
+    /// [`Error::Unsupported`] for every other mode.
+    async fn write_with(&self, id: &str, mode: WriteMode) -> Result<BoxedAsyncWrite<'static>> {
+        match mode {
+            WriteMode::Truncate => self.write(id).await,
+            _ => Err(Error::unsupported(format!(
+                "{mode:?} writes are not supported by this file source"
+            ))),
+        }
+    }
+
+    /// Opens the file `id` to continue writing it where an earlier, interrupted write stopped.
+    ///
+    /// `session` is the [`ResumableWrite::session`] of the interrupted write, without one a new
+    /// write is started. File sources that cannot tell how much of a write arrived start over at
+    /// offset `0`.
+    async fn write_resumable(&self, _id: &str, _session: Option<&str>) -> Result<ResumableWrite> {
+        Err(Error::unsupported(
+            "resumable writes are not supported by this file source",
+        ))
+    }
+
+    async fn create(&self, file_type: &FileType, name: &str, parent_id: &str) -> Result<File>;
+
+    /// Copies the file `id` into `dir_id` as `name` without passing its contents through this
+    /// process, returning the copy.
+    ///
+    /// Copies within the file source fall back to reading and writing the file if this returns
+    /// [`Error::Unsupported`], which is the default.
+    async fn copy(&self, _id: &str, _name: &str, _dir_id: &str) -> Result<File> {
+        Err(Error::unsupported(
+            "copying files is not supported by this file source",
+        ))
+    }
+
+    /// Like [`copy`](Backend::copy), but reports the progress of copies that take a while.
+    fn copy_with_progress<'a>(
+        &'a self,
+        id: &'a str,
+        name: &'a str,
+        dir_id: &'a str,
+    ) -> BoxStream<'a, Result<CopyEvent>> {
+        stream::once(async move { self.copy(id, name, dir_id).await.map(CopyEvent::Done) }).boxed()
+    }
+
+    /// The hex encoded `algorithm` checksum of the file `id`, `None` if the file source has none.
+    ///
+    /// Defaults to the checksum kept in the [`Metadata`] of the file.
+    async fn checksum(&self, id: &str, algorithm: Checksum) -> Result<Option<String>> {
+        let meta = self.get(id).await?.metadata;
+
+        Ok(match algorithm {
+            Checksum::Md5 => meta.md5,
+            Checksum::Sha256 => meta.sha256,
+        })
+    }
+
+    /// Creates a new name `name` in `dir_id` for the file `id`.
+    async fn hard_link(&self, _id: &str, _name: &str, _dir_id: &str) -> Result<File> {
+        Err(Error::unsupported(
+            "hard links are not supported by this file source",
+        ))
+    }
+
+    async fn rename(&self, id: &str, new_name: &str) -> Result<()>;
+
+    /// Moves the file `id` into `dir_id` as `name`, returning the moved file.
+    ///
+    /// Fails with [`Error::AlreadyExists`] if `dir_id` has a file named `name` that is not `id`
+    /// itself, where the file source can tell.
+    async fn mv(&self, id: &str, dir_id: &str, name: &str) -> Result<File>;
+
+    /// Like [`mv`](Backend::mv), but reports the progress of moves that have to copy the file.
+    fn mv_with_progress<'a>(
+        &'a self,
+        id: &'a str,
+        dir_id: &'a str,
+        name: &'a str,
+    ) -> BoxStream<'a, Result<MoveProgress>> {
+        stream::once(async move { self.mv(id, dir_id, name).await.map(MoveProgress::Done) }).boxed()
+    }
+
+    /// Moves the file `id` to the trash of the file source.
+    async fn trash(&self, _id: &str) -> Result<TrashedFile> {
+        Err(Error::unsupported(
+            "the trash is not supported by this file source",
+        ))
+    }
+
+    /// Lists the files in the trash of the file source.
+    fn list_trash(&self) -> BoxStream<'_, Result<TrashedFile>> {
+        stream::once(async {
+            Err(Error::unsupported(
+                "the trash is not supported by this file source",
+            ))
+        })
+        .boxed()
+    }
+
+    /// Moves the trashed file `id` back to where it was trashed from, returning the restored file.
+    async fn restore(&self, _id: &str) -> Result<File> {
+        Err(Error::unsupported(
+            "the trash is not supported by this file source",
+        ))
+    }
+
+    /// Deletes everything in the trash of the file source for good.
+    async fn empty_trash(&self) -> Result<()> {
+        Err(Error::unsupported(
+            "the trash is not supported by this file source",
+        ))
+    }
+
+    /// Reports the changes in the directory `dir_id` until the stream is dropped, see
+    /// [`watch`](crate::watch).
+    fn watch<'a>(
+        &'a self,
+        _dir_id: &'a str,
+        _options: &'a WatchOptions,
+    ) -> BoxStream<'a, Result<WatchEvent>> {
+        stream::once(async {
+            Err(Error::unsupported(
+                "watching directories is not supported by this file source",
+            ))
+        })
+        .boxed()
+    }
+
+    async fn delete_file(&self, id: &str) -> Result<()>;
+
+    async fn delete_dir(&self, id: &str) -> Result<()>;
+
+    async fn mime(&self, id: &str) -> Result<String>;
+}
+
+/// A writer returned by [`Backend::write_resumable`].
+pub struct ResumableWrite {
+    pub writer: BoxedAsyncWrite<'static>,
+    /// The offset in the file the writer continues at, everything before it was already written.
+    pub offset: u64,
+    /// Identifies the write to continue it later, if the file source needs that.
+    pub session: Option<String>,
+}
+
+/// Registers `backend` so that files with a `FileSource::Custom(name)` source are handled by it.
+///
+/// Registering a backend under a name that is already taken replaces the previous backend.
+pub async fn register_backend(name: impl Into<String>, backend: impl Backend + 'static) {
+    BACKENDS
+        .write()
+        .await
+        .insert(name.into(), Arc::new(backend));
+}
+
+/// Removes the backend registered under `name`, returning it if there was one.
+pub async fn unregister_backend(name: &str) -> Option<Arc<dyn Backend>> {
+    BACKENDS.write().await.remove(name)
+}
+
+pub(crate) async fn backend_for(source: &FileSource) -> Result<Arc<dyn Backend>> {
+    match source {
+        FileSource::Local => Ok(Arc::new(local::Local)),
+        #[cfg(feature = "google_drive")]
+        FileSource::GoogleDrive(config_name) => {
+            Ok(Arc::new(google_drive::GoogleDrive::new(config_name)))
+        }
+        FileSource::Custom(name) => BACKENDS.read().await.get(name).cloned().ok_or_else(|| {
+            Error::not_found(format!("A backend with name {} does not exist", name))
+        }),
+    }
+}
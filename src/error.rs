@@ -0,0 +1,247 @@
+use std::io;
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+pub(crate) type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// The error type for every operation in this crate.
+///
+/// Each variant carries a human readable message describing the operation that failed and, where
+/// there is one, the underlying error as its [`source`](std::error::Error::source).
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum Error {
+    #[error("{message}")]
+    NotFound {
+        message: String,
+        #[source]
+        source: Option<BoxError>,
+    },
+
+    #[error("{message}")]
+    AlreadyExists {
+        message: String,
+        #[source]
+        source: Option<BoxError>,
+    },
+
+    #[error("{message}")]
+    PermissionDenied {
+        message: String,
+        #[source]
+        source: Option<BoxError>,
+    },
+
+    #[error("{message}")]
+    NotADirectory {
+        message: String,
+        #[source]
+        source: Option<BoxError>,
+    },
+
+    #[error("{message}")]
+    IsADirectory {
+        message: String,
+        #[source]
+        source: Option<BoxError>,
+    },
+
+    #[error("{message}")]
+    DirectoryNotEmpty {
+        message: String,
+        #[source]
+        source: Option<BoxError>,
+    },
+
+    /// The storage quota of the account, or the free space of the disk, is exhausted.
+    #[error("{message}")]
+    QuotaExceeded {
+        message: String,
+        #[source]
+        source: Option<BoxError>,
+    },
+
+    /// Too many requests were sent in a short time, the operation may be retried later.
+    #[error("{message}")]
+    RateLimited {
+        message: String,
+        #[source]
+        source: Option<BoxError>,
+    },
+
+    /// The credentials used for the request are invalid, expired or have been revoked.
+    #[error("{message}")]
+    AuthExpired {
+        message: String,
+        #[source]
+        source: Option<BoxError>,
+    },
+
+    /// The operation is not supported by the file source or for this file type.
+    #[error("{message}")]
+    Unsupported {
+        message: String,
+        #[source]
+        source: Option<BoxError>,
+    },
+
+    /// A copy does not match its source.
+    #[error("{message}")]
+    VerificationFailed {
+        message: String,
+        #[source]
+        source: Option<BoxError>,
+    },
+
+    /// The checksum of a copy does not match the checksum of its source.
+    #[error("{message}")]
+    ChecksumMismatch {
+        message: String,
+        #[source]
+        source: Option<BoxError>,
+    },
+
+    /// The operation was cancelled through a [`CancellationToken`](crate::CancellationToken).
+    #[error("{message}")]
+    Cancelled {
+        message: String,
+        #[source]
+        source: Option<BoxError>,
+    },
+
+    #[error("{message}")]
+    Network {
+        message: String,
+        #[source]
+        source: Option<BoxError>,
+    },
+
+    /// Any other I/O error.
+    #[error("{message}")]
+    Io {
+        message: String,
+        #[source]
+        source: io::Error,
+    },
+
+    #[error(transparent)]
+    Other(anyhow::Error),
+}
+
+impl Error {
+    pub(crate) fn unsupported(message: impl Into<String>) -> Self {
+        Self::Unsupported {
+            message: message.into(),
+            source: None,
+        }
+    }
+
+    pub(crate) fn cancelled(message: impl Into<String>) -> Self {
+        Self::Cancelled {
+            message: message.into(),
+            source: None,
+        }
+    }
+
+    pub(crate) fn not_found(message: impl Into<String>) -> Self {
+        Self::NotFound {
+            message: message.into(),
+            source: None,
+        }
+    }
+
+    pub(crate) fn already_exists(message: impl Into<String>) -> Self {
+        Self::AlreadyExists {
+            message: message.into(),
+            source: None,
+        }
+    }
+
+    pub(crate) fn not_a_directory(message: impl Into<String>) -> Self {
+        Self::NotADirectory {
+            message: message.into(),
+            source: None,
+        }
+    }
+
+    /// Maps an I/O error to the variant matching its [`io::ErrorKind`].
+    ///
+    /// If `source` only wraps an [`Error`], e.g. one returned by an `AsyncWrite` implementation of
+    /// this crate, that error is returned as is.
+    pub(crate) fn from_io(source: io::Error, message: impl Into<String>) -> Self {
+        if source.get_ref().is_some_and(|e| e.is::<Error>()) {
+            let inner = source.into_inner().unwrap().downcast::<Error>().unwrap();
+            return *inner;
+        }
+
+        let message = message.into();
+
+        macro_rules! kind {
+            ($variant:ident) => {
+                Self::$variant {
+                    message,
+                    source: Some(Box::new(source)),
+                }
+            };
+        }
+
+        match source.kind() {
+            io::ErrorKind::NotFound => kind!(NotFound),
+            io::ErrorKind::AlreadyExists => kind!(AlreadyExists),
+            io::ErrorKind::PermissionDenied => kind!(PermissionDenied),
+            io::ErrorKind::NotADirectory => kind!(NotADirectory),
+            io::ErrorKind::IsADirectory => kind!(IsADirectory),
+            io::ErrorKind::DirectoryNotEmpty => kind!(DirectoryNotEmpty),
+            io::ErrorKind::StorageFull | io::ErrorKind::QuotaExceeded => kind!(QuotaExceeded),
+            io::ErrorKind::Unsupported => kind!(Unsupported),
+            _ => Self::Io { message, source },
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        let message = e.to_string();
+        Self::from_io(e, message)
+    }
+}
+
+impl From<anyhow::Error> for Error {
+    fn from(e: anyhow::Error) -> Self {
+        match e.downcast::<Error>() {
+            Ok(e) => e,
+            Err(e) => Self::Other(e),
+        }
+    }
+}
+
+impl From<tokio::task::JoinError> for Error {
+    fn from(e: tokio::task::JoinError) -> Self {
+        Self::Other(e.into())
+    }
+}
+
+#[cfg(feature = "google_drive")]
+impl From<reqwest::Error> for Error {
+    fn from(e: reqwest::Error) -> Self {
+        if e.is_decode() {
+            return Self::Other(e.into());
+        }
+
+        Self::Network {
+            message: e.to_string(),
+            source: Some(Box::new(e)),
+        }
+    }
+}
+
+/// Attaches a message to the errors of fallible I/O operations, mapping them to an [`Error`].
+pub(crate) trait Context<T> {
+    fn with_context<F: FnOnce() -> String>(self, f: F) -> Result<T>;
+}
+
+impl<T> Context<T> for std::result::Result<T, io::Error> {
+    fn with_context<F: FnOnce() -> String>(self, f: F) -> Result<T> {
+        self.map_err(|e| Error::from_io(e, f()))
+    }
+}
@@ -3,30 +3,40 @@ use crate::{
     *,
 };
 
-use anyhow::Result;
 use async_stream::try_stream;
 use futures::{Stream, TryStreamExt};
 use reqwest::{header::*, Response};
-use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite};
 use tokio_util::compat::FuturesAsyncReadCompatExt;
 
-use super::{oauth, HTTP};
+use super::{
+    oauth,
+    utils::{self, ResponseExt},
+    HTTP,
+};
 
 pub const RES_URI: &str = "https://www.googleapis.com/drive/v3/files";
 pub const UPLOAD_URI: &str = "https://www.googleapis.com/upload/drive/v3/files";
+pub const CHANGES_URI: &str = "https://www.googleapis.com/drive/v3/changes";
 
 lazy_static::lazy_static! {
     static ref GET_FIELDS: String = DriveFile::fields().join(",");
-    static ref LIST_FIELDS: String = format!("files({})", GET_FIELDS.as_str());
+    static ref LIST_FIELDS: String = format!("nextPageToken,files({})", GET_FIELDS.as_str());
+    static ref CHANGE_FIELDS: String = format!(
+        "nextPageToken,newStartPageToken,changes(fileId,removed,file({}))",
+        GET_FIELDS.as_str()
+    );
 }
 
 pub async fn get_meta(config_name: &str, id: &str) -> Result<File> {
     let f = HTTP
-        .get(&format!("{RES_URI}/{id}"))
+        .get(format!("{RES_URI}/{id}"))
         .query(&[("fields", GET_FIELDS.as_str())])
         .header(AUTHORIZATION, &oauth::get_auth_header(config_name).await?)
         .send()
         .await?
+        .check()
+        .await?
         .json::<DriveFile>()
         .await?;
 
@@ -35,51 +45,306 @@ pub async fn get_meta(config_name: &str, id: &str) -> Result<File> {
 
 pub async fn read(config_name: &str, id: &str) -> Result<impl AsyncRead> {
     let s = HTTP
-        .get(&format!("{RES_URI}/{id}"))
+        .get(format!("{RES_URI}/{id}"))
+        .query(&[("alt", "media")])
+        .header(AUTHORIZATION, &oauth::get_auth_header(config_name).await?)
+        .send()
+        .await?
+        .check()
+        .await?
+        .bytes_stream()
+        .map_err(futures::io::Error::other)
+        .into_async_read()
+        .compat();
+
+    Ok(s)
+}
+
+/// Reads `len` bytes of the file `id` starting at `offset`, `len` must not be `0`.
+pub async fn read_range(
+    config_name: &str,
+    id: &str,
+    offset: u64,
+    len: u64,
+) -> Result<impl AsyncRead> {
+    let end = offset.saturating_add(len - 1);
+
+    let s = HTTP
+        .get(format!("{RES_URI}/{id}"))
         .query(&[("alt", "media")])
         .header(AUTHORIZATION, &oauth::get_auth_header(config_name).await?)
+        .header(RANGE, format!("bytes={offset}-{end}"))
         .send()
         .await?
+        .check()
+        .await?
         .bytes_stream()
-        .map_err(|e| futures::io::Error::new(futures::io::ErrorKind::Other, e))
+        .map_err(futures::io::Error::other)
         .into_async_read()
         .compat();
 
     Ok(s)
 }
 
-pub async fn write<'a>(config_name: &'a str, id: &'a str) -> Result<impl AsyncWrite + 'a> {
+pub async fn write(config_name: &str, id: &str) -> Result<impl AsyncWrite> {
+    let upload_url = start_upload(config_name, id).await?;
+
+    Ok(Upload::new(upload_url, config_name.to_owned()))
+}
+
+/// Opens the file `id` for writing with `mode`, which for every mode but `Truncate` and `CreateNew`
+/// uploads the kept contents of the file again.
+pub async fn write_with(
+    config_name: &str,
+    id: &str,
+    mode: WriteMode,
+) -> Result<BoxedAsyncWrite<'static>> {
+    let size = match mode {
+        WriteMode::Truncate => return Ok(Box::pin(write(config_name, id).await?)),
+        WriteMode::CreateNew => {
+            if get_meta(config_name, id).await?.size > 0 {
+                return Err(Error::already_exists(format!("'{}' is not empty", id)));
+            }
+            return Ok(Box::pin(write(config_name, id).await?));
+        }
+        _ => get_meta(config_name, id).await?.size,
+    };
+    let pos = match mode {
+        WriteMode::WriteAt(offset) => offset,
+        _ => size,
+    };
+
+    let mut upload = Upload::new(start_upload(config_name, id).await?, config_name.to_owned());
+    let context = || format!("Could not write to file '{}'", id);
+
+    // the contents before the written part, padded with zeros if it starts after the end
+    let head = pos.min(size);
+    if head > 0 {
+        let mut r = Box::pin(read_range(config_name, id, 0, head).await?);
+        tokio::io::copy(&mut r, &mut upload)
+            .await
+            .with_context(context)?;
+    }
+    if pos > size {
+        tokio::io::copy(&mut tokio::io::repeat(0).take(pos - size), &mut upload)
+            .await
+            .with_context(context)?;
+    }
+
+    Ok(Box::pin(Splice::new(
+        upload,
+        config_name.to_owned(),
+        id.to_owned(),
+        pos,
+        size,
+    )))
+}
+
+/// Continues the upload session `session` of the file `id`, or starts a new one without it.
+///
+/// Drive forgets upload sessions after about a week, continuing an expired session starts a new one
+/// from the beginning.
+pub async fn write_resumable(config_name: &str, id: &str, session: Option<&str>) -> Result<Upload> {
+    if let Some(url) = session {
+        match committed(config_name, url).await {
+            Ok(Some(sent)) => {
+                return Ok(Upload::resume(url.to_owned(), config_name.to_owned(), sent))
+            }
+            Ok(None) => {
+                let size = get_meta(config_name, id).await?.size;
+                return Ok(Upload::finished(
+                    url.to_owned(),
+                    config_name.to_owned(),
+                    size,
+                ));
+            }
+            Err(Error::NotFound { .. }) => {}
+            Err(e) => return Err(e),
+        }
+    }
+
+    let url = start_upload(config_name, id).await?;
+    Ok(Upload::resume(url, config_name.to_owned(), 0))
+}
+
+async fn start_upload(config_name: &str, id: &str) -> Result<String> {
     let upload_url = HTTP
-        .patch(&format!("{UPLOAD_URI}/{id}"))
+        .patch(format!("{UPLOAD_URI}/{id}"))
         .query(&[("uploadType", "resumable")])
         .header(AUTHORIZATION, &oauth::get_auth_header(config_name).await?)
         .send()
         .await?
-        .error_for_status()?
+        .check()
+        .await?
         .headers()
         .get(LOCATION)
         .ok_or_else(|| anyhow::anyhow!("unexpected response with no `Location` header"))?
-        .to_str()?
+        .to_str()
+        .map_err(anyhow::Error::new)?
         .to_owned();
 
-    Ok(Upload::new(upload_url, config_name))
+    Ok(upload_url)
+}
+
+// The number of bytes the upload session `upload_url` received so far, `None` if the upload is
+// already complete.
+async fn committed(config_name: &str, upload_url: &str) -> Result<Option<u64>> {
+    let res = HTTP
+        .put(upload_url)
+        .header(AUTHORIZATION, &oauth::get_auth_header(config_name).await?)
+        .header(CONTENT_LENGTH, 0)
+        .header(CONTENT_RANGE, "bytes */*")
+        .send()
+        .await?
+        .check()
+        .await?;
+
+    if res.status().is_success() {
+        return Ok(None);
+    }
+
+    // without a range header nothing was received yet
+    match res.headers().get(RANGE) {
+        None => Ok(Some(0)),
+        Some(range) => {
+            let range = range.to_str().map_err(anyhow::Error::new)?;
+            let (_, end) = utils::parse_range_header(range)?;
+            Ok(Some(end + 1))
+        }
+    }
 }
 
 pub fn list_meta<'a>(
     config_name: &'a str,
     parent_id: &'a str,
 ) -> impl Stream<Item = Result<File>> + 'a {
+    list_matching(
+        config_name,
+        format!(
+            "parents in '{}' and trashed = false",
+            utils::escape_query(parent_id)
+        ),
+    )
+}
+
+/// A page of the files in `parent_id` as described by `options`, whose cursors are Drive page
+/// tokens.
+pub async fn list_page(config_name: &str, parent_id: &str, options: &ListOptions) -> Result<Page> {
+    let q = format!(
+        "parents in '{}' and trashed = false",
+        utils::escape_query(parent_id)
+    );
+    let order_by = utils::order_by(options.sort, options.direction);
+    let page_size = options.page_size.unwrap_or(1000).clamp(1, 1000);
+    let page_token = options.cursor.as_ref().map(|c| c.to_string());
+
+    let res = list(
+        config_name,
+        &q,
+        order_by.as_deref(),
+        page_size,
+        page_token.as_deref(),
+    )
+    .await?
+    .check()
+    .await?
+    .json::<ListResponse>()
+    .await?;
+
+    // hidden files are only hidden by their names, which Drive cannot search for
+    let files = res
+        .files
+        .into_iter()
+        .map(|f| File::from((f, config_name)))
+        .filter(|f| options.include_hidden || !f.metadata.hidden)
+        .collect();
+
+    Ok(Page {
+        files,
+        next: res.next_page_token.map(Cursor::from),
+    })
+}
+
+/// The file named `name` in `parent_id`, if there is one.
+pub async fn find(config_name: &str, parent_id: &str, name: &str) -> Result<Option<File>> {
+    let q = format!(
+        "parents in '{}' and name = '{}' and trashed = false",
+        utils::escape_query(parent_id),
+        utils::escape_query(name)
+    );
+
+    let files = list_matching(config_name, q);
+    futures::pin_mut!(files);
+    files.try_next().await
+}
+
+/// The files matching `query` in `root_id` and all folders below it.
+///
+/// The folders are searched a batch at a time, each batch taking one request for its subfolders
+/// and one for the matching files in it.
+pub fn search<'a>(
+    config_name: &'a str,
+    root_id: &'a str,
+    query: &'a Query,
+) -> impl Stream<Item = Result<File>> + 'a {
+    const BATCH: usize = 50;
+
+    try_stream! {
+        let terms = utils::search_terms(query);
+        let mut folders = vec![root_id.to_owned()];
+        let mut searched = 0;
+
+        while searched < folders.len() {
+            let batch = &folders[searched..folders.len().min(searched + BATCH)];
+            searched += batch.len();
+
+            let parents = batch
+                .iter()
+                .map(|id| format!("'{}' in parents", utils::escape_query(id)))
+                .collect::<Vec<_>>()
+                .join(" or ");
+
+            let q = format!("({parents}) and mimeType = '{FOLDER}' and trashed = false");
+            let subfolders = list_matching(config_name, q).try_collect::<Vec<_>>().await?;
+
+            let q = [format!("({parents})"), "trashed = false".to_owned()]
+                .into_iter()
+                .chain(terms.iter().cloned())
+                .collect::<Vec<_>>()
+                .join(" and ");
+            for await f in list_matching(config_name, q) {
+                let f = f?;
+                // Drive cannot search by size, and matches names a bit differently
+                if query.matches_metadata(&f) {
+                    yield f;
+                }
+            }
+
+            folders.extend(subfolders.into_iter().map(|f| f.id.1));
+        }
+    }
+}
+
+// The files matching the search query `q`.
+fn list_matching(config_name: &str, q: String) -> impl Stream<Item = Result<File>> + '_ {
+    list_drive_files(config_name, q).map_ok(move |f| File::from((f, config_name)))
+}
+
+// The Drive files matching the search query `q`, fetched a page at a time.
+fn list_drive_files(config_name: &str, q: String) -> impl Stream<Item = Result<DriveFile>> + '_ {
     let mut next_page_token: Option<String> = None;
 
     try_stream! {
         loop {
-            let res = list(config_name, parent_id, next_page_token.as_deref())
+            let res = list(config_name, &q, None, 1000, next_page_token.as_deref())
+                .await?
+                .check()
                 .await?
                 .json::<ListResponse>()
                 .await?;
 
             for f in res.files.into_iter() {
-                yield File::from((f, config_name));
+                yield f;
             }
 
             match res.next_page_token {
@@ -100,6 +365,28 @@ pub async fn create_file(config_name: &str, file_name: &str, parent_dir: &str) -
         }))
         .send()
         .await?
+        .check()
+        .await?
+        .json::<DriveFile>()
+        .await?;
+
+    Ok((f, config_name).into())
+}
+
+/// Copies the file `id` into `parent_dir` as `file_name` on the Drive servers.
+pub async fn copy(config_name: &str, id: &str, file_name: &str, parent_dir: &str) -> Result<File> {
+    let f = HTTP
+        .post(format!("{RES_URI}/{id}/copy"))
+        .query(&[("fields", GET_FIELDS.as_str())])
+        .header(AUTHORIZATION, &oauth::get_auth_header(config_name).await?)
+        .json(&serde_json::json!({
+            "name": file_name,
+            "parents": [parent_dir],
+        }))
+        .send()
+        .await?
+        .check()
+        .await?
         .json::<DriveFile>()
         .await?;
 
@@ -117,6 +404,8 @@ pub async fn create_dir(config_name: &str, dir_name: &str, parent_dir: &str) ->
         }))
         .send()
         .await?
+        .check()
+        .await?
         .json::<DriveFile>()
         .await?;
 
@@ -124,46 +413,152 @@ pub async fn create_dir(config_name: &str, dir_name: &str, parent_dir: &str) ->
 }
 
 pub async fn rename(config_name: &str, id: &str, new_name: &str) -> Result<()> {
-    HTTP.patch(&format!("{RES_URI}/{id}"))
+    HTTP.patch(format!("{RES_URI}/{id}"))
         .header(AUTHORIZATION, &oauth::get_auth_header(config_name).await?)
         .json(&serde_json::json!({ "name": new_name }))
         .send()
         .await?
-        .error_for_status()
+        .check()
+        .await
         .map(|_r| ())
-        .map_err(anyhow::Error::new)
 }
 
-pub async fn mv(config_name: &str, id: &str, new_parent: &str) -> Result<()> {
-    HTTP.patch(&format!("{RES_URI}/{id}"))
-        .query(&[("addParents", new_parent)])
+pub async fn mv(config_name: &str, id: &str, new_parent: &str, name: &str) -> Result<File> {
+    let old_parent = get_meta(config_name, id).await?.parent_id.map(|p| p.1);
+
+    let f = HTTP
+        .patch(format!("{RES_URI}/{id}"))
+        .query(&[
+            ("addParents", new_parent),
+            ("removeParents", old_parent.as_deref().unwrap_or_default()),
+            ("fields", GET_FIELDS.as_str()),
+        ])
         .header(AUTHORIZATION, &oauth::get_auth_header(config_name).await?)
-        .json("{}")
+        .json(&serde_json::json!({ "name": name }))
         .send()
         .await?
-        .error_for_status()
-        .map(|_r| ())
-        .map_err(anyhow::Error::new)
+        .check()
+        .await?
+        .json::<DriveFile>()
+        .await?;
+
+    Ok((f, config_name).into())
 }
 
 pub async fn delete(config_name: &str, id: &str) -> Result<()> {
-    HTTP.delete(&format!("{RES_URI}/{id}"))
+    HTTP.delete(format!("{RES_URI}/{id}"))
         .header(AUTHORIZATION, &oauth::get_auth_header(config_name).await?)
         .send()
         .await?
-        .error_for_status()
-        .map_err(anyhow::Error::new)
+        .check()
+        .await
         .map(|_r| ())
 }
 
+pub async fn trash(config_name: &str, id: &str) -> Result<TrashedFile> {
+    let f = set_trashed(config_name, id, true).await?;
+    Ok((f, config_name).into())
+}
+
+/// The files in the trash that were trashed themselves, the files in trashed folders are left out.
+pub fn list_trash(config_name: &str) -> impl Stream<Item = Result<TrashedFile>> + '_ {
+    list_drive_files(config_name, "trashed = true".to_owned())
+        .try_filter(|f| futures::future::ready(f.explicitly_trashed.unwrap_or(true)))
+        .map_ok(move |f| TrashedFile::from((f, config_name)))
+}
+
+pub async fn restore(config_name: &str, id: &str) -> Result<File> {
+    let f = set_trashed(config_name, id, false).await?;
+    Ok((f, config_name).into())
+}
+
+pub async fn empty_trash(config_name: &str) -> Result<()> {
+    HTTP.delete(format!("{RES_URI}/trash"))
+        .header(AUTHORIZATION, &oauth::get_auth_header(config_name).await?)
+        .send()
+        .await?
+        .check()
+        .await
+        .map(|_r| ())
+}
+
+async fn set_trashed(config_name: &str, id: &str, trashed: bool) -> Result<DriveFile> {
+    let f = HTTP
+        .patch(format!("{RES_URI}/{id}"))
+        .query(&[("fields", GET_FIELDS.as_str())])
+        .header(AUTHORIZATION, &oauth::get_auth_header(config_name).await?)
+        .json(&serde_json::json!({ "trashed": trashed }))
+        .send()
+        .await?
+        .check()
+        .await?
+        .json::<DriveFile>()
+        .await?;
+
+    Ok(f)
+}
+
+/// The page token that [`list_changes`] reports the changes made from now on with.
+pub async fn start_page_token(config_name: &str) -> Result<String> {
+    let res = HTTP
+        .get(format!("{CHANGES_URI}/startPageToken"))
+        .header(AUTHORIZATION, &oauth::get_auth_header(config_name).await?)
+        .send()
+        .await?
+        .check()
+        .await?
+        .json::<StartPageToken>()
+        .await?;
+
+    Ok(res.start_page_token)
+}
+
+/// All changes since `page_token` and the page token to ask for the changes after them with.
+pub async fn list_changes(config_name: &str, page_token: &str) -> Result<(Vec<Change>, String)> {
+    let mut changes = vec![];
+    let mut page_token = page_token.to_owned();
+
+    loop {
+        let res = HTTP
+            .get(CHANGES_URI)
+            .header(AUTHORIZATION, &oauth::get_auth_header(config_name).await?)
+            .query(&[
+                ("fields", CHANGE_FIELDS.as_str()),
+                ("pageToken", page_token.as_str()),
+                ("pageSize", "1000"),
+                ("includeRemoved", "true"),
+                ("spaces", "drive"),
+            ])
+            .send()
+            .await?
+            .check()
+            .await?
+            .json::<ChangeList>()
+            .await?;
+
+        changes.extend(res.changes);
+
+        match (res.next_page_token, res.new_start_page_token) {
+            (Some(next), _) => page_token = next,
+            (None, Some(start)) => return Ok((changes, start)),
+            (None, None) => {
+                return Err(
+                    anyhow::anyhow!("Google Drive sent no page token for the next changes").into(),
+                )
+            }
+        }
+    }
+}
+
 pub async fn get_mime(config_name: &str, id: &str) -> Result<String> {
     let f = HTTP
-        .get(&format!("{RES_URI}/{id}"))
+        .get(format!("{RES_URI}/{id}"))
         .query(&[("fields", GET_FIELDS.as_str())])
         .header(AUTHORIZATION, &oauth::get_auth_header(config_name).await?)
         .send()
         .await?
-        .error_for_status()?
+        .check()
+        .await?
         .json::<DriveFile>()
         .await?;
 
@@ -188,24 +583,28 @@ pub async fn add_config(
     );
 }
 
-async fn list(name: &str, parent_id: &str, page_token: Option<&str>) -> Result<Response> {
-    let req = HTTP
+async fn list(
+    name: &str,
+    q: &str,
+    order_by: Option<&str>,
+    page_size: usize,
+    page_token: Option<&str>,
+) -> Result<Response> {
+    let mut req = HTTP
         .get(RES_URI)
-        .header(AUTHORIZATION, &oauth::get_auth_header(name).await?);
-
-    let req = match page_token {
-        None => req.query(&[
-            ("fields", LIST_FIELDS.as_str()),
-            ("q", format!("parents in '{parent_id}'").as_str()),
-            ("pageSize", "1000"),
-        ]),
-        Some(s) => req.query(&[
+        .header(AUTHORIZATION, &oauth::get_auth_header(name).await?)
+        .query(&[
             ("fields", LIST_FIELDS.as_str()),
-            ("q", format!("parents in '{parent_id}'").as_str()),
-            ("pageSize", "1000"),
-            ("pageToken", s),
-        ]),
-    };
+            ("q", q),
+            ("pageSize", page_size.to_string().as_str()),
+        ]);
+
+    if let Some(o) = order_by {
+        req = req.query(&[("orderBy", o)]);
+    }
+    if let Some(s) = page_token {
+        req = req.query(&[("pageToken", s)]);
+    }
 
-    req.send().await.map_err(anyhow::Error::new)
+    req.send().await.map_err(Error::from)
 }
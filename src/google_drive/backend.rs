@@ -0,0 +1,134 @@
+use async_trait::async_trait;
+use futures::{stream::BoxStream, StreamExt};
+
+use crate::{google_drive as gd, *};
+
+/// The backend for files on a Google Drive account, identified by the name of its config.
+pub struct GoogleDrive {
+    config_name: String,
+}
+
+impl GoogleDrive {
+    pub fn new(config_name: &str) -> Self {
+        Self {
+            config_name: config_name.to_owned(),
+        }
+    }
+}
+
+#[async_trait]
+impl Backend for GoogleDrive {
+    async fn get(&self, id: &str) -> Result<File> {
+        gd::get_meta(&self.config_name, id).await
+    }
+
+    fn list<'a>(
+        &'a self,
+        dir_id: &'a str,
+        _options: &'a ListOptions,
+    ) -> BoxStream<'a, Result<File>> {
+        gd::list_meta(&self.config_name, dir_id).boxed()
+    }
+
+    async fn list_page(&self, dir_id: &str, options: &ListOptions) -> Result<Page> {
+        gd::list_page(&self.config_name, dir_id, options).await
+    }
+
+    fn search<'a>(&'a self, root_id: &'a str, query: &'a Query) -> BoxStream<'a, Result<File>> {
+        gd::search(&self.config_name, root_id, query).boxed()
+    }
+
+    async fn read(&self, id: &str) -> Result<BoxedAsyncRead<'static>> {
+        gd::read(&self.config_name, id)
+            .await
+            .map(|r| Box::pin(r) as _)
+    }
+
+    async fn read_range(&self, id: &str, offset: u64, len: u64) -> Result<BoxedAsyncRead<'static>> {
+        gd::read_range(&self.config_name, id, offset, len)
+            .await
+            .map(|r| Box::pin(r) as _)
+    }
+
+    async fn write(&self, id: &str) -> Result<BoxedAsyncWrite<'static>> {
+        gd::write(&self.config_name, id)
+            .await
+            .map(|w| Box::pin(w) as _)
+    }
+
+    async fn write_with(&self, id: &str, mode: WriteMode) -> Result<BoxedAsyncWrite<'static>> {
+        gd::write_with(&self.config_name, id, mode).await
+    }
+
+    async fn write_resumable(&self, id: &str, session: Option<&str>) -> Result<ResumableWrite> {
+        let upload = gd::write_resumable(&self.config_name, id, session).await?;
+
+        Ok(ResumableWrite {
+            offset: upload.sent(),
+            session: Some(upload.url().to_owned()),
+            writer: Box::pin(upload),
+        })
+    }
+
+    async fn create(&self, file_type: &FileType, name: &str, parent_id: &str) -> Result<File> {
+        match file_type {
+            FileType::File => gd::create_file(&self.config_name, name, parent_id).await,
+            FileType::Dir => gd::create_dir(&self.config_name, name, parent_id).await,
+            _ => Err(Error::unsupported(
+                "creating this file type is currently not supported",
+            )),
+        }
+    }
+
+    async fn copy(&self, id: &str, name: &str, dir_id: &str) -> Result<File> {
+        gd::copy(&self.config_name, id, name, dir_id).await
+    }
+
+    async fn rename(&self, id: &str, new_name: &str) -> Result<()> {
+        gd::rename(&self.config_name, id, new_name).await
+    }
+
+    async fn find(&self, dir_id: &str, name: &str) -> Result<Option<File>> {
+        gd::find(&self.config_name, dir_id, name).await
+    }
+
+    async fn mv(&self, id: &str, dir_id: &str, name: &str) -> Result<File> {
+        gd::mv(&self.config_name, id, dir_id, name).await
+    }
+
+    async fn trash(&self, id: &str) -> Result<TrashedFile> {
+        gd::trash(&self.config_name, id).await
+    }
+
+    fn list_trash(&self) -> BoxStream<'_, Result<TrashedFile>> {
+        gd::list_trash(&self.config_name).boxed()
+    }
+
+    async fn restore(&self, id: &str) -> Result<File> {
+        gd::restore(&self.config_name, id).await
+    }
+
+    async fn empty_trash(&self) -> Result<()> {
+        gd::empty_trash(&self.config_name).await
+    }
+
+    fn watch<'a>(
+        &'a self,
+        dir_id: &'a str,
+        options: &'a WatchOptions,
+    ) -> BoxStream<'a, Result<WatchEvent>> {
+        gd::watch(&self.config_name, dir_id, options).boxed()
+    }
+
+    async fn delete_file(&self, id: &str) -> Result<()> {
+        gd::delete(&self.config_name, id).await
+    }
+
+    async fn delete_dir(&self, id: &str) -> Result<()> {
+        gd::delete(&self.config_name, id).await
+    }
+
+    async fn mime(&self, id: &str) -> Result<String> {
+        gd::get_mime(&self.config_name, id).await
+    }
+}
@@ -1,7 +1,9 @@
 mod api;
+mod backend;
 mod oauth;
 mod types;
 mod utils;
+mod watch;
 
 use std::collections::HashMap;
 
@@ -9,7 +11,9 @@ use reqwest::Client;
 use tokio::sync::RwLock;
 
 pub use api::*;
+pub use backend::GoogleDrive;
 pub use types::*;
+pub use watch::watch;
 
 lazy_static::lazy_static! {
     pub static ref CONFIGS: RwLock<HashMap<String, Config>> = RwLock::new(HashMap::<String, Config>::new());
@@ -1,10 +1,10 @@
-use crate::google_drive::CONFIGS;
+use crate::{google_drive::CONFIGS, Error, Result};
 
-pub async fn get_auth_header(name: &str) -> anyhow::Result<String> {
+pub async fn get_auth_header(name: &str) -> Result<String> {
     let c = CONFIGS.read().await;
     let config = c
         .get(name)
-        .ok_or_else(|| anyhow::anyhow!("A config with name {} does not exist", name))?;
+        .ok_or_else(|| Error::not_found(format!("A config with name {} does not exist", name)))?;
 
     if config.is_valid()? {
         return Ok(format!("Bearer {}", config.access_token));
@@ -1,9 +1,10 @@
 use std::time::{Duration, UNIX_EPOCH};
 
-use anyhow::{Context, Result};
+use anyhow::Context;
 use serde::Deserialize;
 
 use super::RefreshToken;
+use crate::{Error, Result};
 
 const TOKEN_URI: &str = "https://oauth2.googleapis.com/token";
 
@@ -28,7 +29,7 @@ impl Config {
     }
 
     pub async fn refresh(&mut self) -> Result<()> {
-        let token = crate::google_drive::HTTP
+        let res = crate::google_drive::HTTP
             .post(TOKEN_URI)
             .form(&[
                 ("client_id", self.client_id.as_str()),
@@ -37,11 +38,20 @@ impl Config {
                 ("refresh_token", self.refresh_token.as_str()),
             ])
             .send()
-            .await
-            .with_context(|| format!("Could not send post request to '{}'", TOKEN_URI))?
-            .json::<RefreshToken>()
             .await?;
 
+        // the token endpoint answers with an OAuth error body rather than a Drive one, and a
+        // client error here means the refresh token is no longer usable
+        if res.status().is_client_error() {
+            let message = res.text().await?;
+            return Err(Error::AuthExpired {
+                message: format!("Could not refresh access token: {}", message),
+                source: None,
+            });
+        }
+
+        let token = res.error_for_status()?.json::<RefreshToken>().await?;
+
         let expires_at = UNIX_EPOCH
             .elapsed()
             .with_context(|| "Time went backwards!")?
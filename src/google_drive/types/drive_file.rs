@@ -1,9 +1,11 @@
+use std::time::SystemTime;
+
 use fievar::Fields;
 use serde::Deserialize;
 
 use crate::*;
 
-const FOLDER: &str = "application/vnd.google-apps.folder";
+pub const FOLDER: &str = "application/vnd.google-apps.folder";
 
 #[derive(Debug, Deserialize, Fields)]
 pub struct DriveFile {
@@ -14,6 +16,45 @@ pub struct DriveFile {
     pub mime_type: String,
     pub size: Option<String>,
     pub parents: Option<Vec<String>>,
+    #[serde(rename = "createdTime")]
+    #[fievar(name = "createdTime")]
+    pub created_time: Option<String>,
+    #[serde(rename = "modifiedTime")]
+    #[fievar(name = "modifiedTime")]
+    pub modified_time: Option<String>,
+    #[serde(rename = "viewedByMeTime")]
+    #[fievar(name = "viewedByMeTime")]
+    pub viewed_by_me_time: Option<String>,
+    #[fievar(name = "owners(displayName,emailAddress)")]
+    pub owners: Option<Vec<Owner>>,
+    #[fievar(name = "capabilities(canEdit)")]
+    pub capabilities: Option<Capabilities>,
+    #[serde(rename = "md5Checksum")]
+    #[fievar(name = "md5Checksum")]
+    pub md5_checksum: Option<String>,
+    #[serde(rename = "sha256Checksum")]
+    #[fievar(name = "sha256Checksum")]
+    pub sha256_checksum: Option<String>,
+    pub trashed: Option<bool>,
+    #[serde(rename = "trashedTime")]
+    #[fievar(name = "trashedTime")]
+    pub trashed_time: Option<String>,
+    #[serde(rename = "explicitlyTrashed")]
+    #[fievar(name = "explicitlyTrashed")]
+    pub explicitly_trashed: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Owner {
+    pub display_name: Option<String>,
+    pub email_address: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Capabilities {
+    pub can_edit: Option<bool>,
 }
 
 impl From<(DriveFile, &str)> for File {
@@ -31,6 +72,23 @@ impl From<(DriveFile, &str)> for File {
             _ => FileType::File,
         };
 
+        let owner = file
+            .owners
+            .and_then(|o| o.into_iter().next())
+            .and_then(|o| o.display_name.or(o.email_address));
+
+        let metadata = Metadata {
+            modified: parse_time(file.modified_time),
+            created: parse_time(file.created_time),
+            accessed: parse_time(file.viewed_by_me_time),
+            owner,
+            hidden: file.name.starts_with('.'),
+            read_only: !file.capabilities.and_then(|c| c.can_edit).unwrap_or(true),
+            md5: file.md5_checksum,
+            sha256: file.sha256_checksum,
+            ..Default::default()
+        };
+
         let id = FileId(file_source, file.id);
 
         Self {
@@ -39,6 +97,26 @@ impl From<(DriveFile, &str)> for File {
             id,
             size,
             parent_id,
+            metadata,
         }
     }
 }
+
+impl From<(DriveFile, &str)> for TrashedFile {
+    fn from((mut file, source_name): (DriveFile, &str)) -> Self {
+        let trashed = parse_time(file.trashed_time.take());
+        let file = File::from((file, source_name));
+
+        // trashed files keep their parents
+        Self {
+            original_parent: file.parent_id.clone(),
+            original_name: file.name.clone(),
+            file,
+            trashed,
+        }
+    }
+}
+
+fn parse_time(time: Option<String>) -> Option<SystemTime> {
+    time.and_then(|t| humantime::parse_rfc3339(&t).ok())
+}
@@ -0,0 +1,27 @@
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize)]
+pub struct ErrorResponse {
+    pub error: DriveError,
+}
+
+/// The error object returned in the body of failed Drive API requests.
+#[derive(Debug, Deserialize, thiserror::Error)]
+#[error("{code}: {message}")]
+pub struct DriveError {
+    pub code: u16,
+    pub message: String,
+    #[serde(default)]
+    pub errors: Vec<ErrorDetail>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ErrorDetail {
+    pub reason: Option<String>,
+}
+
+impl DriveError {
+    pub fn reason(&self) -> Option<&str> {
+        self.errors.iter().find_map(|e| e.reason.as_deref())
+    }
+}
@@ -1,9 +1,13 @@
 mod config;
 mod drive_file;
+mod error;
+mod splice;
 mod upload;
 
 pub use config::Config;
-pub use drive_file::DriveFile;
+pub use drive_file::{DriveFile, FOLDER};
+pub use error::{DriveError, ErrorResponse};
+pub use splice::Splice;
 pub use upload::Upload;
 
 use serde::Deserialize;
@@ -14,6 +18,29 @@ pub struct RefreshToken {
     pub expires_in: u64,
 }
 
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StartPageToken {
+    pub start_page_token: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChangeList {
+    pub next_page_token: Option<String>,
+    pub new_start_page_token: Option<String>,
+    pub changes: Vec<Change>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Change {
+    pub file_id: String,
+    #[serde(default)]
+    pub removed: bool,
+    pub file: Option<DriveFile>,
+}
+
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ListResponse {
@@ -0,0 +1,101 @@
+use std::{
+    io,
+    pin::Pin,
+    task::{ready, Context, Poll},
+};
+
+use futures::{future::BoxFuture, FutureExt};
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+
+use super::Upload;
+use crate::google_drive::{read_range, utils::IntoIOErr};
+
+/// An upload that writes over a part of a file. The kept contents before the written part are
+/// uploaded before anything is written, those after it once the upload is shut down.
+pub struct Splice {
+    upload: Option<Upload>,
+    config_name: String,
+    id: String,
+    // the offset in the file the next write goes to
+    pos: u64,
+    // the size of the file before the upload
+    size: u64,
+    shutdown: Option<BoxFuture<'static, io::Result<()>>>,
+}
+
+impl Splice {
+    /// Continues `upload` of the file `id` at offset `pos`, keeping the original contents after
+    /// what is written up to `size`.
+    pub fn new(upload: Upload, config_name: String, id: String, pos: u64, size: u64) -> Self {
+        Self {
+            upload: Some(upload),
+            config_name,
+            id,
+            pos,
+            size,
+            shutdown: None,
+        }
+    }
+
+    fn upload(&mut self) -> io::Result<&mut Upload> {
+        self.upload
+            .as_mut()
+            .ok_or_else(|| io::Error::other("the upload was already shut down"))
+    }
+}
+
+impl AsyncWrite for Splice {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+
+        let n = ready!(Pin::new(this.upload()?).poll_write(cx, buf))?;
+        this.pos += n as u64;
+
+        Poll::Ready(Ok(n))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+
+        match &mut this.upload {
+            Some(upload) => Pin::new(upload).poll_flush(cx),
+            None => Poll::Ready(Ok(())),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+
+        if this.shutdown.is_none() {
+            // without either the upload was already shut down
+            let Some(mut upload) = this.upload.take() else {
+                return Poll::Ready(Ok(()));
+            };
+            let (config_name, id) = (this.config_name.clone(), this.id.clone());
+            let (pos, size) = (this.pos, this.size);
+
+            this.shutdown = Some(
+                async move {
+                    if pos < size {
+                        let tail = read_range(&config_name, &id, pos, size - pos)
+                            .await
+                            .map_err(IntoIOErr::into_io_err)?;
+                        tokio::io::copy(&mut Box::pin(tail), &mut upload).await?;
+                    }
+
+                    upload.shutdown().await
+                }
+                .boxed(),
+            );
+        }
+
+        let r = ready!(this.shutdown.as_mut().unwrap().poll_unpin(cx));
+        this.shutdown = None;
+
+        Poll::Ready(r)
+    }
+}
@@ -1,49 +1,84 @@
 use std::{
-    io,
+    io, mem,
     pin::Pin,
     task::{Context, Poll},
 };
 
-use anyhow::Result;
 use futures::{future::BoxFuture, Future, FutureExt};
 use reqwest::header::*;
 use tokio::io::AsyncWrite;
 
-use crate::google_drive::{
-    oauth::get_auth_header,
-    utils::{parse_range_header, IntoIOErr},
-    HTTP,
+use crate::{
+    google_drive::{
+        oauth::get_auth_header,
+        utils::{parse_range_header, IntoIOErr, ResponseExt},
+        HTTP,
+    },
+    Error, Result,
 };
 
 // 512 KB
 const BUF_SIZE: usize = (256 * 1024) * 2;
 
-pub struct Upload<'a> {
+pub struct Upload {
     upload_url: String,
-    config_name: &'a str,
+    config_name: String,
     sent: u64,
     buf: Vec<u8>,
-    state: State<'a>,
+    state: State,
+    // whether the whole file was uploaded
+    done: bool,
+    // whether the session outlives the upload, so it can be continued later
+    resumable: bool,
 }
 
-enum State<'a> {
+enum State {
     // in this state the buffer is never full
     Buffering,
     // in this state the buffer is always full
-    Uploading(BoxFuture<'a, Result<u64>>),
+    Uploading(BoxFuture<'static, Result<u64>>),
 }
 
-impl<'a> Upload<'a> {
-    pub fn new(upload_url: String, config_name: &'a str) -> Upload<'a> {
+impl Upload {
+    pub fn new(upload_url: String, config_name: String) -> Upload {
         Self {
             upload_url,
             config_name,
             sent: 0,
             buf: Vec::with_capacity(BUF_SIZE),
             state: State::Buffering,
+            done: false,
+            resumable: false,
         }
     }
 
+    /// Continues the session `upload_url`, which already received the first `sent` bytes.
+    ///
+    /// Unlike uploads created with [`new`](Upload::new), the session is kept when the upload is
+    /// dropped before it is done.
+    pub fn resume(upload_url: String, config_name: String, sent: u64) -> Upload {
+        let mut upload = Self::new(upload_url, config_name);
+        upload.sent = sent;
+        upload.resumable = true;
+        upload
+    }
+
+    /// An upload of `size` bytes to the session `upload_url` that already completed.
+    pub fn finished(upload_url: String, config_name: String, size: u64) -> Upload {
+        let mut upload = Self::resume(upload_url, config_name, size);
+        upload.done = true;
+        upload
+    }
+
+    /// The number of bytes the session received so far.
+    pub fn sent(&self) -> u64 {
+        self.sent
+    }
+
+    pub fn url(&self) -> &str {
+        &self.upload_url
+    }
+
     fn upload(&mut self, size: Option<u64>) -> impl Future<Output = Result<u64>> {
         let upload_url = unsafe { &*std::ptr::addr_of!(*self.upload_url) };
         let config_name = unsafe { &*std::ptr::addr_of!(*self.config_name) };
@@ -52,9 +87,11 @@ impl<'a> Upload<'a> {
 
         let len = buf.len() as u64;
         let range_start = self.sent;
-        let range_end = (range_start + len) - 1;
+        let range_end = (range_start + len).saturating_sub(1);
         let content_range = match size {
             None => format!("bytes {range_start}-{range_end}/*"),
+            // finishing an upload without sending more bytes
+            Some(size) if len == 0 => format!("bytes */{size}"),
             Some(size) => format!("bytes {range_start}-{range_end}/{size}"),
         };
 
@@ -67,7 +104,8 @@ impl<'a> Upload<'a> {
                 .body(&buf[..])
                 .send()
                 .await?
-                .error_for_status()?;
+                .check()
+                .await?;
 
             if res.status().is_success() {
                 let s = buf.len() as u64;
@@ -80,7 +118,8 @@ impl<'a> Upload<'a> {
                 .headers()
                 .get(RANGE)
                 .ok_or_else(|| anyhow::anyhow!("unexpected response with no range header"))?
-                .to_str()?;
+                .to_str()
+                .map_err(anyhow::Error::new)?;
             let (start, end) = parse_range_header(range)?;
             *sent = end + 1;
 
@@ -92,7 +131,7 @@ impl<'a> Upload<'a> {
         let filled = self.buf.len();
 
         if filled >= BUF_SIZE {
-            return Err(anyhow::anyhow!("buffer is already filled"));
+            return Err(Error::Other(anyhow::anyhow!("buffer is already filled")));
         }
 
         let src_len = src.len();
@@ -108,13 +147,13 @@ impl<'a> Upload<'a> {
     }
 }
 
-impl<'a> AsyncWrite for Upload<'a> {
+impl AsyncWrite for Upload {
     fn poll_write(
         self: Pin<&mut Self>,
         cx: &mut Context<'_>,
         src: &[u8],
     ) -> Poll<io::Result<usize>> {
-        let mut this = self.get_mut();
+        let this = self.get_mut();
 
         match &mut this.state {
             State::Buffering => {
@@ -148,6 +187,10 @@ impl<'a> AsyncWrite for Upload<'a> {
     fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
         let this = self.get_mut();
 
+        if this.done {
+            return Ok(()).into();
+        }
+
         match &mut this.state {
             State::Buffering => {
                 let size = this.sent + this.buf.len() as u64;
@@ -164,6 +207,7 @@ impl<'a> AsyncWrite for Upload<'a> {
 
                     if this.buf.is_empty() {
                         this.state = State::Buffering;
+                        this.done = true;
                         return Ok(()).into();
                     }
 
@@ -176,3 +220,30 @@ impl<'a> AsyncWrite for Upload<'a> {
         }
     }
 }
+
+// nothing can continue an unfinished upload that is not resumable once it is dropped, so its
+// session is cancelled instead of lingering until it expires
+impl Drop for Upload {
+    fn drop(&mut self) {
+        if self.done || self.resumable {
+            return;
+        }
+
+        let Ok(rt) = tokio::runtime::Handle::try_current() else {
+            return;
+        };
+
+        let upload_url = mem::take(&mut self.upload_url);
+        let config_name = mem::take(&mut self.config_name);
+
+        rt.spawn(async move {
+            if let Ok(auth) = get_auth_header(&config_name).await {
+                let _ = HTTP
+                    .delete(upload_url)
+                    .header(AUTHORIZATION, auth)
+                    .send()
+                    .await;
+            }
+        });
+    }
+}
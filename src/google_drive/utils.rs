@@ -1,22 +1,73 @@
 use std::io;
 
+use async_trait::async_trait;
+use reqwest::{Response, StatusCode};
+
+use crate::{google_drive::types::*, Error, FileType, Query, Result, SortDirection, SortKey};
+
 pub trait IntoIOErr {
     fn into_io_err(self) -> io::Error;
 }
 
-impl IntoIOErr for anyhow::Error {
+impl IntoIOErr for Error {
     fn into_io_err(self) -> io::Error {
-        let e = self.downcast::<io::Error>();
-        match e {
-            Ok(e) => e,
-            Err(e) => io::Error::new(io::ErrorKind::Other, e),
+        io::Error::other(self)
+    }
+}
+
+#[async_trait]
+pub trait ResponseExt: Sized {
+    /// Turns a response with an error status into an [`Error`] built from the Drive error body.
+    async fn check(self) -> Result<Self>;
+}
+
+#[async_trait]
+impl ResponseExt for Response {
+    async fn check(self) -> Result<Self> {
+        let status = self.status();
+
+        if !status.is_client_error() && !status.is_server_error() {
+            return Ok(self);
         }
+
+        let body = self.text().await?;
+        Err(drive_error(status, &body))
     }
 }
 
-impl IntoIOErr for reqwest::Error {
-    fn into_io_err(self) -> io::Error {
-        io::Error::new(io::ErrorKind::Other, self)
+pub fn drive_error(status: StatusCode, body: &str) -> Error {
+    let source = match serde_json::from_str::<ErrorResponse>(body) {
+        Ok(r) => r.error,
+        Err(_) => DriveError {
+            code: status.as_u16(),
+            message: status.canonical_reason().unwrap_or("unknown error").into(),
+            errors: vec![],
+        },
+    };
+
+    let message = source.message.clone();
+
+    macro_rules! kind {
+        ($variant:ident) => {
+            Error::$variant {
+                message,
+                source: Some(Box::new(source)),
+            }
+        };
+    }
+
+    match (status, source.reason()) {
+        (_, Some("storageQuotaExceeded" | "quotaExceeded" | "teamDriveFileLimitExceeded")) => {
+            kind!(QuotaExceeded)
+        }
+        (_, Some("userRateLimitExceeded" | "rateLimitExceeded" | "sharingRateLimitExceeded"))
+        | (StatusCode::TOO_MANY_REQUESTS, _) => kind!(RateLimited),
+        (StatusCode::UNAUTHORIZED, _) => kind!(AuthExpired),
+        (StatusCode::FORBIDDEN, _) => kind!(PermissionDenied),
+        (StatusCode::NOT_FOUND, _) => kind!(NotFound),
+        (StatusCode::CONFLICT, _) => kind!(AlreadyExists),
+        (s, _) if s.is_server_error() => kind!(Network),
+        _ => Error::Other(anyhow::Error::new(source)),
     }
 }
 
@@ -36,8 +87,67 @@ pub fn parse_range_header(range: &str) -> anyhow::Result<(u64, u64)> {
     Ok((start, end))
 }
 
+/// Escapes `s` to be quoted in a search query.
+pub fn escape_query(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('\'', "\\'")
+}
+
+/// The search query terms for the fields of `query` Drive can search by.
+///
+/// The times are cut to milliseconds, which is what Drive keeps, so the terms may match a few
+/// files more than `query`.
+pub fn search_terms(query: &Query) -> Vec<String> {
+    let time = |t| humantime::format_rfc3339_millis(t);
+    let mut terms = vec![];
+
+    if let Some(name) = &query.name_contains {
+        terms.push(format!("name contains '{}'", escape_query(name)));
+    }
+    if let Some(mime_type) = &query.mime_type {
+        terms.push(format!("mimeType = '{}'", escape_query(mime_type)));
+    }
+    match query.file_type {
+        Some(FileType::Dir) => terms.push(format!("mimeType = '{FOLDER}'")),
+        Some(FileType::File) => terms.push(format!("mimeType != '{FOLDER}'")),
+        _ => {}
+    }
+    if let Some(t) = query.modified_after {
+        terms.push(format!("modifiedTime > '{}'", time(t)));
+    }
+    if let Some(t) = query.modified_before {
+        terms.push(format!("modifiedTime <= '{}'", time(t)));
+    }
+
+    terms
+}
+
+/// The `orderBy` parameter for listing files sorted by `key`, `None` to leave them unsorted.
+pub fn order_by(key: SortKey, direction: SortDirection) -> Option<String> {
+    let keys: &[&str] = match key {
+        SortKey::Unsorted => return None,
+        SortKey::Name => &["name_natural"],
+        SortKey::Size => &["quotaBytesUsed", "name_natural"],
+        SortKey::Modified => &["modifiedTime", "name_natural"],
+        SortKey::DirsFirst => &["folder", "name_natural"],
+    };
+    let suffix = match direction {
+        SortDirection::Ascending => "",
+        SortDirection::Descending => " desc",
+    };
+
+    Some(
+        keys.iter()
+            .map(|k| format!("{k}{suffix}"))
+            .collect::<Vec<_>>()
+            .join(","),
+    )
+}
+
 #[cfg(test)]
 mod tests {
+    use reqwest::StatusCode;
+
+    use crate::Error;
 
     #[test]
     fn test_parse_range_header() -> anyhow::Result<()> {
@@ -45,8 +155,68 @@ mod tests {
         assert_eq!(range, (100, 200));
 
         let range = super::parse_range_header("");
-        assert!(matches!(range, Err(_)));
+        assert!(range.is_err());
 
         Ok(())
     }
+
+    #[test]
+    fn test_drive_error() {
+        let body = r#"{
+            "error": {
+                "code": 403,
+                "message": "The user's Drive storage quota has been exceeded.",
+                "errors": [{ "reason": "storageQuotaExceeded" }]
+            }
+        }"#;
+        let e = super::drive_error(StatusCode::FORBIDDEN, body);
+        assert!(matches!(e, Error::QuotaExceeded { .. }));
+
+        let body = r#"{ "error": { "code": 403, "message": "Forbidden" } }"#;
+        let e = super::drive_error(StatusCode::FORBIDDEN, body);
+        assert!(matches!(e, Error::PermissionDenied { .. }));
+
+        let e = super::drive_error(StatusCode::NOT_FOUND, "not json");
+        assert!(matches!(e, Error::NotFound { .. }));
+    }
+
+    #[test]
+    fn test_search_terms() {
+        use std::time::{Duration, UNIX_EPOCH};
+
+        let query = crate::Query {
+            name_contains: Some("it's".into()),
+            file_type: Some(crate::FileType::Dir),
+            min_size: Some(1),
+            modified_after: Some(UNIX_EPOCH + Duration::from_millis(1500)),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            super::search_terms(&query),
+            [
+                r"name contains 'it\'s'",
+                "mimeType = 'application/vnd.google-apps.folder'",
+                "modifiedTime > '1970-01-01T00:00:01.500Z'",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_order_by() {
+        use crate::{SortDirection::*, SortKey::*};
+
+        assert_eq!(super::order_by(Unsorted, Descending), None);
+        assert_eq!(super::order_by(Name, Ascending).unwrap(), "name_natural");
+        assert_eq!(
+            super::order_by(DirsFirst, Descending).unwrap(),
+            "folder desc,name_natural desc"
+        );
+    }
+
+    #[test]
+    fn test_escape_query() {
+        assert_eq!(super::escape_query("it's"), r"it\'s");
+        assert_eq!(super::escape_query(r"a\b"), r"a\\b");
+    }
 }
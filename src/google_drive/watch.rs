@@ -0,0 +1,361 @@
+use std::{borrow::Cow, collections::HashMap, io, path::Path, time::Duration};
+
+use async_stream::try_stream;
+use futures::{Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    google_drive::{api, types::*},
+    *,
+};
+
+/// Polls the changes of the Drive for the ones in `dir_id`, see [`watch`](crate::watch).
+pub fn watch<'a>(
+    config_name: &'a str,
+    dir_id: &'a str,
+    options: &'a WatchOptions,
+) -> impl Stream<Item = Result<WatchEvent>> + 'a {
+    try_stream! {
+        let saved = match &options.cursor_file {
+            Some(path) => load(path).await?,
+            None => None,
+        };
+        let root = api::get_meta(config_name, dir_id).await?.id.1;
+
+        let (mut token, mut tree) = match saved {
+            // the tree as of the saved token, so the changes since then apply to it
+            Some(s) if s.root == root && s.recursive == options.recursive => {
+                let tree = Tree {
+                    config_name,
+                    root,
+                    recursive: s.recursive,
+                    files: s.files.into_owned(),
+                };
+                (s.token.into_owned(), tree)
+            }
+            saved => {
+                let token = match saved {
+                    Some(s) => s.token.into_owned(),
+                    None => api::start_page_token(config_name).await?,
+                };
+
+                // the tree is listed after the token was taken, so no change in between is missed
+                let tree = Tree::new(config_name, root, dir_id, options.recursive).await?;
+                save(&token, &tree, options).await?;
+                (token, tree)
+            }
+        };
+
+        loop {
+            let (changes, next) = list_changes(config_name, &token).await?;
+            let changed = !changes.is_empty() || next != token;
+
+            for change in changes {
+                if change.file_id == tree.root && is_gone(&change) {
+                    yield WatchEvent::Deleted(tree.id(dir_id));
+                    return;
+                }
+
+                for e in tree.apply(change).await? {
+                    yield e;
+                }
+            }
+
+            // all events of the poll were taken once the stream is polled again
+            token = next;
+            if changed {
+                save(&token, &tree, options).await?;
+            }
+
+            tokio::time::sleep(options.poll_interval).await;
+        }
+    }
+}
+
+// How often asking for the changes is retried after a network error or being rate limited, waiting
+// twice as long after every attempt.
+const RETRIES: u32 = 6;
+const FIRST_RETRY: Duration = Duration::from_secs(1);
+
+async fn list_changes(config_name: &str, token: &str) -> Result<(Vec<Change>, String)> {
+    let mut wait = FIRST_RETRY;
+
+    for _ in 0..RETRIES {
+        match api::list_changes(config_name, token).await {
+            Err(Error::Network { .. } | Error::RateLimited { .. }) => {
+                tokio::time::sleep(wait).await;
+                wait *= 2;
+            }
+            r => return r,
+        }
+    }
+
+    api::list_changes(config_name, token).await
+}
+
+// What the cursor file keeps: the page token and the files known to be in the watched folder as
+// of that token.
+#[derive(Serialize, Deserialize)]
+struct Saved<'a> {
+    token: Cow<'a, str>,
+    root: Cow<'a, str>,
+    recursive: bool,
+    files: Cow<'a, HashMap<String, Known>>,
+}
+
+// The files known to be in the watched folder, to tell the changes in it from the others.
+struct Tree<'a> {
+    config_name: &'a str,
+    // the id of the watched folder, which may be given as an alias like `root`
+    root: String,
+    recursive: bool,
+    files: HashMap<String, Known>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct Known {
+    name: String,
+    parent: Option<String>,
+    is_dir: bool,
+}
+
+impl<'a> Tree<'a> {
+    async fn new(
+        config_name: &'a str,
+        root: String,
+        dir_id: &str,
+        recursive: bool,
+    ) -> Result<Self> {
+        let mut tree = Self {
+            config_name,
+            root,
+            recursive,
+            files: HashMap::new(),
+        };
+        tree.add(dir_id).await?;
+
+        Ok(tree)
+    }
+
+    fn id(&self, id: &str) -> FileId {
+        FileId(
+            FileSource::GoogleDrive(self.config_name.to_owned()),
+            id.to_owned(),
+        )
+    }
+
+    // Adds everything in the folder `dir_id`, returning what was added.
+    async fn add(&mut self, dir_id: &str) -> Result<Vec<File>> {
+        let options = WalkOptions {
+            max_depth: (!self.recursive).then_some(1),
+            ..Default::default()
+        };
+
+        let mut added = vec![];
+        let id = self.id(dir_id);
+        let entries = walk(&id, options);
+        futures::pin_mut!(entries);
+
+        while let Some(entry) = entries.next().await {
+            let file = entry?.file;
+            self.insert(&file);
+            added.push(file);
+        }
+
+        Ok(added)
+    }
+
+    fn insert(&mut self, file: &File) {
+        self.files.insert(
+            file.id.1.clone(),
+            Known {
+                name: file.name.clone(),
+                parent: file.parent_id.as_ref().map(|p| p.1.clone()),
+                is_dir: file.file_type == FileType::Dir,
+            },
+        );
+    }
+
+    // Forgets `id` and everything in it.
+    fn remove(&mut self, id: &str) {
+        let mut children = HashMap::<&str, Vec<&str>>::new();
+        for (id, f) in &self.files {
+            if let Some(parent) = &f.parent {
+                children.entry(parent).or_default().push(id);
+            }
+        }
+
+        // `gone` grows with the children of its entries while it is gone through
+        let mut gone = vec![id];
+        let mut i = 0;
+        while let Some(&id) = gone.get(i) {
+            gone.extend(children.get(id).into_iter().flatten());
+            i += 1;
+        }
+
+        let gone = gone.into_iter().map(str::to_owned).collect::<Vec<_>>();
+        for id in gone {
+            self.files.remove(&id);
+        }
+    }
+
+    fn contains_dir(&self, id: &str) -> bool {
+        id == self.root || (self.recursive && self.files.get(id).is_some_and(|f| f.is_dir))
+    }
+
+    // The events for `change`, which may be anywhere on the Drive.
+    async fn apply(&mut self, change: Change) -> Result<Vec<WatchEvent>> {
+        let id = change.file_id.clone();
+
+        if is_gone(&change) {
+            return Ok(match self.files.contains_key(&id) {
+                true => {
+                    self.remove(&id);
+                    vec![WatchEvent::Deleted(self.id(&id))]
+                }
+                false => vec![],
+            });
+        }
+
+        let Some(file) = change.file else {
+            return Ok(vec![]);
+        };
+        let file = File::from((file, self.config_name));
+        let parent = file.parent_id.as_ref().map(|p| p.1.clone());
+        let inside = parent.as_deref().is_some_and(|p| self.contains_dir(p));
+
+        let events = match (self.files.get(&id), inside) {
+            (Some(known), true) => {
+                let moved = known.name != file.name || known.parent != parent;
+                self.insert(&file);
+
+                match moved {
+                    true => vec![WatchEvent::Renamed {
+                        from: file.id.clone(),
+                        to: file,
+                    }],
+                    false => vec![WatchEvent::Modified(file)],
+                }
+            }
+            (Some(_), false) => {
+                self.remove(&id);
+                vec![WatchEvent::Deleted(file.id)]
+            }
+            (None, true) => {
+                self.insert(&file);
+                let is_dir = file.file_type == FileType::Dir;
+                let mut events = vec![WatchEvent::Created(file)];
+
+                // a folder moved into the watched one brings its files along
+                if is_dir && self.recursive {
+                    events.extend(self.add(&id).await?.into_iter().map(WatchEvent::Created));
+                }
+                events
+            }
+            (None, false) => vec![],
+        };
+
+        Ok(events)
+    }
+}
+
+fn is_gone(change: &Change) -> bool {
+    change.removed
+        || change
+            .file
+            .as_ref()
+            .is_none_or(|f| f.trashed.unwrap_or(false))
+}
+
+async fn load(path: &Path) -> Result<Option<Saved<'static>>> {
+    let context = || format!("Could not read cursor file '{}'", path.to_string_lossy());
+
+    let json = match tokio::fs::read(path).await {
+        Ok(json) => json,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(Error::from_io(e, context())),
+    };
+
+    serde_json::from_slice(&json)
+        .map(Some)
+        .map_err(io::Error::from)
+        .with_context(context)
+}
+
+async fn save(token: &str, tree: &Tree<'_>, options: &WatchOptions) -> Result<()> {
+    let Some(path) = &options.cursor_file else {
+        return Ok(());
+    };
+    let context = || format!("Could not write cursor file '{}'", path.to_string_lossy());
+
+    let saved = Saved {
+        token: Cow::Borrowed(token),
+        root: Cow::Borrowed(&tree.root),
+        recursive: tree.recursive,
+        files: Cow::Borrowed(&tree.files),
+    };
+    let json = serde_json::to_vec(&saved)
+        .map_err(io::Error::from)
+        .with_context(context)?;
+    local::write_atomic(path, &json).await.with_context(context)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    fn tree(files: &[(&str, &str, bool)]) -> Tree<'static> {
+        let files = files.iter().map(|&(id, parent, is_dir)| {
+            let known = Known {
+                name: id.to_owned(),
+                parent: Some(parent.to_owned()),
+                is_dir,
+            };
+            (id.to_owned(), known)
+        });
+
+        Tree {
+            config_name: "test",
+            root: "root".into(),
+            recursive: true,
+            files: files.collect(),
+        }
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut tree = tree(&[
+            ("a", "root", true),
+            ("b", "a", true),
+            ("c", "b", false),
+            ("d", "a", false),
+            ("e", "root", false),
+        ]);
+
+        tree.remove("a");
+        assert_eq!(tree.files.keys().collect::<Vec<_>>(), ["e"]);
+    }
+
+    #[tokio::test]
+    async fn test_cursor_file() {
+        let tmp = TempDir::new("drive-cursor");
+        let path = tmp.path("cursor");
+        assert!(load(&path).await.unwrap().is_none());
+
+        let options = WatchOptions {
+            cursor_file: Some(path.clone()),
+            ..Default::default()
+        };
+        save("token", &tree(&[("a", "root", false)]), &options)
+            .await
+            .unwrap();
+        let saved = load(&path).await.unwrap().unwrap();
+        assert_eq!(saved.token, "token");
+        assert_eq!(saved.root, "root");
+        assert!(saved.files.contains_key("a"));
+
+        tmp.write("cursor", "token");
+        assert!(load(&path).await.is_err());
+    }
+}
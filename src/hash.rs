@@ -0,0 +1,97 @@
+use std::{
+    fmt::Write,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+};
+
+use md5::{Digest, Md5};
+use sha2::Sha256;
+use tokio::io::{AsyncRead, ReadBuf};
+
+use crate::Checksum;
+
+#[derive(Clone)]
+pub enum Hasher {
+    Md5(Md5),
+    Sha256(Sha256),
+}
+
+impl Hasher {
+    pub fn new(algorithm: Checksum) -> Self {
+        match algorithm {
+            Checksum::Md5 => Self::Md5(Md5::new()),
+            Checksum::Sha256 => Self::Sha256(Sha256::new()),
+        }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        match self {
+            Self::Md5(h) => h.update(data),
+            Self::Sha256(h) => h.update(data),
+        }
+    }
+
+    /// The hex encoded hash of everything passed to [`update`](Hasher::update).
+    pub fn finish(self) -> String {
+        let hash = match self {
+            Self::Md5(h) => h.finalize().to_vec(),
+            Self::Sha256(h) => h.finalize().to_vec(),
+        };
+
+        hash.iter().fold(String::new(), |mut hex, b| {
+            let _ = write!(hex, "{b:02x}");
+            hex
+        })
+    }
+}
+
+/// Hashes everything read through it.
+pub struct HashReader<R> {
+    inner: R,
+    hasher: Arc<Mutex<Hasher>>,
+}
+
+impl<R> HashReader<R> {
+    pub fn new(inner: R, hasher: Arc<Mutex<Hasher>>) -> Self {
+        Self { inner, hasher }
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for HashReader<R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        let this = self.get_mut();
+        let filled = buf.filled().len();
+
+        let res = Pin::new(&mut this.inner).poll_read(cx, buf);
+        if let Poll::Ready(Ok(())) = res {
+            this.hasher.lock().unwrap().update(&buf.filled()[filled..]);
+        }
+
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_finish() {
+        let mut h = Hasher::new(Checksum::Md5);
+        h.update(b"hello");
+        assert_eq!(h.finish(), "5d41402abc4b2a76b9719d911017c592");
+
+        let mut h = Hasher::new(Checksum::Sha256);
+        h.update(b"hel");
+        h.update(b"lo");
+        assert_eq!(
+            h.finish(),
+            "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+        );
+    }
+}
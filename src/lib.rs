@@ -1,9 +1,26 @@
 mod api;
+mod backend;
+mod error;
+mod hash;
 mod local;
+mod sort;
 mod types;
 
+#[cfg(test)]
+mod testing;
+
 #[cfg(feature = "google_drive")]
 mod google_drive;
 
 pub use api::*;
+pub use backend::*;
+pub use error::{Error, Result};
+pub use local::Local;
 pub use types::*;
+
+pub use tokio_util::sync::CancellationToken;
+
+pub(crate) use error::Context;
+
+#[cfg(feature = "google_drive")]
+pub use google_drive::{add_config, GoogleDrive};
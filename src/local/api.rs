@@ -1,19 +1,28 @@
-use std::path;
+use std::{io, path};
 
-use anyhow::{Context, Result};
-use async_stream::stream;
-use futures::{Stream, TryStreamExt};
+use async_stream::{stream, try_stream};
+use futures::{Stream, StreamExt, TryStreamExt};
 use tokio::{
     fs,
-    io::{AsyncRead, AsyncWrite},
+    io::{AsyncRead, AsyncReadExt, AsyncSeekExt, AsyncWrite},
     task,
 };
 use tokio_stream::wrappers as tsw;
 use unwrap_or::unwrap_ok_or;
 
-use crate::*;
+use super::{
+    copy::{copy_file, copy_preserving},
+    sys,
+};
+use crate::{hash::Hasher, *};
 
 pub async fn get_meta(path: &path::Path) -> Result<File> {
+    read_meta(path, false).await
+}
+
+/// Like [`get_meta`], but describes the target of `path` instead if it is a symbolic link and
+/// `follow_links` is set.
+pub async fn read_meta(path: &path::Path, follow_links: bool) -> Result<File> {
     let id = path.to_string_lossy().to_string();
     let parent_id = path
         .parent()
@@ -24,18 +33,41 @@ pub async fn get_meta(path: &path::Path) -> Result<File> {
         None => String::from("None"),
     };
 
-    let meta = fs::metadata(path)
+    let meta = fs::symlink_metadata(path)
         .await
         .with_context(|| format!("Could not get metadata for file '{}'", id))?;
 
+    let meta = match follow_links && meta.is_symlink() {
+        // a dangling link is described as a link
+        true => fs::metadata(path).await.unwrap_or(meta),
+        false => meta,
+    };
+
     let size = meta.len();
 
-    let file_type = if meta.is_file() {
+    let ft = meta.file_type();
+    let file_type = if ft.is_file() {
         FileType::File
-    } else if meta.is_dir() {
+    } else if ft.is_dir() {
         FileType::Dir
+    } else if ft.is_symlink() {
+        let target = fs::read_link(path)
+            .await
+            .with_context(|| format!("Could not read link '{}'", id))?;
+
+        FileType::Symlink {
+            target: target.to_string_lossy().to_string(),
+        }
     } else {
-        FileType::Unknown
+        sys::special_file_type(&ft)
+    };
+
+    let metadata = Metadata {
+        modified: meta.modified().ok(),
+        created: meta.created().ok(),
+        accessed: meta.accessed().ok(),
+        hidden: name.starts_with('.'),
+        ..sys::metadata(&meta)
     };
 
     let id = FileId(FileSource::Local, id);
@@ -46,10 +78,20 @@ pub async fn get_meta(path: &path::Path) -> Result<File> {
         file_type,
         size,
         parent_id,
+        metadata,
     })
 }
 
-pub fn list_meta(path: &path::Path) -> impl Stream<Item = Result<File>> + '_ {
+/// The file named `name` in `dir`, if there is one.
+pub async fn find(dir: &path::Path, name: &str) -> Result<Option<File>> {
+    match get_meta(&dir.join(name)).await {
+        Ok(f) => Ok(Some(f)),
+        Err(Error::NotFound { .. }) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+pub fn list_meta(path: &path::Path, follow_links: bool) -> impl Stream<Item = Result<File>> + '_ {
     stream! {
         let id = path.to_string_lossy().to_string();
         let rd = fs::read_dir(path)
@@ -61,15 +103,13 @@ pub fn list_meta(path: &path::Path) -> impl Stream<Item = Result<File>> + '_ {
         });
 
         let s = tsw::ReadDirStream::new(rd)
-            .map_err(move |e| {
-                anyhow::Error::new(e).context(format!("Error while reading directory '{}'", id))
-            })
-            .and_then(|d| async move { get_meta(d.path().as_path()).await });
+            .map_err(move |e| Error::from_io(e, format!("Error while reading directory '{}'", id)))
+            .and_then(move |d| async move { read_meta(d.path().as_path(), follow_links).await });
         for await v in s { yield v; }
     }
 }
 
-pub async fn read(path: &path::Path) -> Result<impl AsyncRead> {
+pub async fn read(path: &path::Path) -> Result<fs::File> {
     let file = fs::File::open(path)
         .await
         .with_context(|| format!("Could not read file '{}'", path.to_string_lossy()))?;
@@ -77,21 +117,111 @@ pub async fn read(path: &path::Path) -> Result<impl AsyncRead> {
     Ok(file)
 }
 
+/// Reads at most `len` bytes of `path` starting at `offset`.
+pub async fn read_range(path: &path::Path, offset: u64, len: u64) -> Result<impl AsyncRead> {
+    let mut file = read(path).await?;
+
+    file.seek(io::SeekFrom::Start(offset))
+        .await
+        .with_context(|| format!("Could not read file '{}'", path.to_string_lossy()))?;
+
+    Ok(file.take(len))
+}
+
 pub async fn write(path: &path::Path) -> Result<impl AsyncWrite> {
-    fs::OpenOptions::new()
-        .create(true)
-        .write(true)
-        .truncate(true)
+    write_with(path, WriteMode::Truncate).await
+}
+
+pub async fn write_with(path: &path::Path, mode: WriteMode) -> Result<impl AsyncWrite> {
+    let context = || format!("Could not write to file '{}'", path.to_string_lossy());
+
+    let mut options = fs::OpenOptions::new();
+    options.write(true);
+    match mode {
+        WriteMode::Truncate => options.create(true).truncate(true),
+        WriteMode::Append => options.create(true).append(true),
+        WriteMode::CreateNew => options.create_new(true),
+        WriteMode::WriteAt(_) => options.create(true),
+    };
+
+    let mut file = options.open(path).await.with_context(context)?;
+    if let WriteMode::WriteAt(offset) = mode {
+        file.seek(io::SeekFrom::Start(offset))
+            .await
+            .with_context(context)?;
+    }
+
+    Ok(file)
+}
+
+/// Opens `path` to write to its end, along with its current size.
+pub async fn append(path: &path::Path) -> Result<(u64, impl AsyncWrite)> {
+    let context = || format!("Could not write to file '{}'", path.to_string_lossy());
+
+    let file = fs::OpenOptions::new()
+        .append(true)
         .open(path)
         .await
-        .with_context(|| format!("Could not write to file '{}'", path.to_string_lossy()))
+        .with_context(context)?;
+    let size = file.metadata().await.with_context(context)?.len();
+
+    Ok((size, file))
+}
+
+/// Replaces the contents of `path` with `contents` through a temporary file next to it, so a
+/// crash while saving leaves either the old or the new contents but never a truncated file.
+///
+/// The temporary file is synced before it is renamed, as otherwise the rename can reach the disk
+/// before the contents do and a crash right after it leaves an empty file behind.
+#[cfg(feature = "serde")]
+pub(crate) async fn write_atomic(path: &path::Path, contents: &[u8]) -> io::Result<()> {
+    use tokio::io::AsyncWriteExt;
+
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+
+    let mut file = fs::File::create(&tmp).await?;
+    file.write_all(contents).await?;
+    file.flush().await?;
+    file.sync_all().await?;
+    drop(file);
+
+    fs::rename(&tmp, path).await
+}
+
+/// Hashes the contents of `path` with `algorithm`.
+pub async fn checksum(path: &path::Path, algorithm: Checksum) -> Result<String> {
+    let path = path.to_path_buf();
+    let context = format!("Could not read file '{}'", path.to_string_lossy());
+
+    task::spawn_blocking(move || {
+        use std::io::Read;
+
+        let mut file = std::fs::File::open(&path)?;
+        let mut hasher = Hasher::new(algorithm);
+        let mut buf = vec![0u8; 1024 * 1024];
+
+        loop {
+            match file.read(&mut buf)? {
+                0 => return Ok(hasher.finish()),
+                n => hasher.update(&buf[..n]),
+            }
+        }
+    })
+    .await?
+    .with_context(|| context)
 }
 
 pub async fn create_file(name: &str, parent: &path::Path) -> Result<File> {
     let mut pb = parent.to_path_buf();
     pb.push(name);
 
-    fs::File::create(pb.as_path()).await?;
+    fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(pb.as_path())
+        .await
+        .with_context(|| format!("Could not create file '{}'", pb.to_string_lossy()))?;
 
     let m = get_meta(pb.as_path()).await?;
 
@@ -102,7 +232,39 @@ pub async fn create_dir(name: &str, parent: &path::Path) -> Result<File> {
     let mut pb = parent.to_path_buf();
     pb.push(name);
 
-    fs::create_dir(pb.as_path()).await?;
+    fs::create_dir(pb.as_path())
+        .await
+        .with_context(|| format!("Could not create directory '{}'", pb.to_string_lossy()))?;
+
+    let m = get_meta(pb.as_path()).await?;
+
+    Ok(m)
+}
+
+pub async fn create_symlink(name: &str, target: &str, parent: &path::Path) -> Result<File> {
+    let mut pb = parent.to_path_buf();
+    pb.push(name);
+
+    sys::symlink(target, pb.as_path())
+        .await
+        .with_context(|| format!("Could not create link '{}'", pb.to_string_lossy()))?;
+
+    let m = get_meta(pb.as_path()).await?;
+
+    Ok(m)
+}
+
+pub async fn create_hard_link(file: &path::Path, name: &str, parent: &path::Path) -> Result<File> {
+    let mut pb = parent.to_path_buf();
+    pb.push(name);
+
+    fs::hard_link(file, pb.as_path()).await.with_context(|| {
+        format!(
+            "Could not link '{}' to '{}'",
+            pb.to_string_lossy(),
+            file.to_string_lossy()
+        )
+    })?;
 
     let m = get_meta(pb.as_path()).await?;
 
@@ -114,40 +276,132 @@ pub async fn rename(file: &path::Path, new_name: &str) -> Result<()> {
     path.set_file_name(new_name);
 
     if path.exists() {
-        return Err(anyhow::anyhow!(
+        return Err(Error::already_exists(format!(
             "A file with name '{}' already exists!",
-            new_name.to_string()
-        ));
+            new_name
+        )));
     }
 
-    fs::rename(file, path.as_path())
+    move_path(file, path)
+        .try_for_each(|_| async { Ok(()) })
         .await
-        .with_context(|| format!("Could not rename file '{}'", file.to_string_lossy()))
 }
 
-pub async fn mv(file: &path::Path, dir: &path::Path) -> Result<()> {
-    let mut dir_pb = path::PathBuf::from(dir);
-    let name = match file.file_name() {
-        Some(n) => n,
-        None => {
-            return Err(anyhow::anyhow!(
-                "Moving files without names is currently not supported!"
-            ));
+pub async fn mv(file: &path::Path, dir: &path::Path, name: &str) -> Result<File> {
+    let s = mv_with_progress(file, dir, name);
+    futures::pin_mut!(s);
+
+    while let Some(p) = s.try_next().await? {
+        if let MoveProgress::Done(f) = p {
+            return Ok(f);
         }
-    };
-    dir_pb.push(name);
+    }
+
+    Err(anyhow::anyhow!("the move of '{}' ended before it was done", file.display()).into())
+}
 
-    let p = dir_pb.to_string_lossy().to_string();
+pub async fn copy(file: &path::Path, name: &str, dir: &path::Path) -> Result<File> {
+    let s = copy_with_progress(file, name, dir);
+    futures::pin_mut!(s);
 
-    fs::rename(file, dir_pb.as_path()).await.with_context(|| {
-        format!(
-            "Could not move file '{}' to '{}'",
-            file.to_string_lossy(),
-            p.clone(),
-        )
-    })?;
+    while let Some(p) = s.try_next().await? {
+        if let CopyEvent::Done(f) = p {
+            return Ok(f);
+        }
+    }
+
+    Err(anyhow::anyhow!("the copy of '{}' ended before it was done", file.display()).into())
+}
+
+pub fn copy_with_progress<'a>(
+    file: &'a path::Path,
+    name: &'a str,
+    dir: &'a path::Path,
+) -> impl Stream<Item = Result<CopyEvent>> + 'a {
+    try_stream! {
+        let dest = create_file(name, dir).await?;
+
+        for await p in copy_file(file, dest) {
+            yield p?;
+        }
+    }
+}
 
-    Ok(())
+pub fn mv_with_progress<'a>(
+    file: &'a path::Path,
+    dir: &'a path::Path,
+    name: &'a str,
+) -> impl Stream<Item = Result<MoveProgress>> + 'a {
+    try_stream! {
+        let to = dir.join(name);
+
+        // a rename would silently replace `to`
+        if to != file && fs::symlink_metadata(&to).await.is_ok() {
+            Err(Error::already_exists(format!(
+                "A file with name '{}' already exists!",
+                name
+            )))?;
+        }
+
+        for await p in move_path(file, to) {
+            yield p?;
+        }
+    }
+}
+
+// Renames `from` to `to`, falling back to copying and deleting `from` when `to` is on another
+// file system.
+//
+// Unlike a rename, the fallback does not replace an existing `to`.
+fn move_path(
+    from: &path::Path,
+    to: path::PathBuf,
+) -> impl Stream<Item = Result<MoveProgress>> + '_ {
+    try_stream! {
+        let context = || {
+            format!(
+                "Could not move file '{}' to '{}'",
+                from.to_string_lossy(),
+                to.to_string_lossy()
+            )
+        };
+
+        match fs::rename(from, &to).await {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::CrossesDevices => {
+                if fs::symlink_metadata(&to).await.is_ok() {
+                    Err(Error::already_exists(context()))?;
+                }
+
+                let s = copy_preserving(from, &to);
+                futures::pin_mut!(s);
+
+                while let Some(p) = s.next().await {
+                    match p {
+                        Ok(p) => yield MoveProgress::Copying(p),
+                        Err(e) => {
+                            // the partial copy is useless, and `to` did not exist before
+                            let _ = remove_all(&to).await;
+                            Err(e)?;
+                        }
+                    }
+                }
+
+                yield MoveProgress::Deleting;
+                remove_all(from).await.with_context(context)?;
+            }
+            Err(e) => Err(Error::from_io(e, context()))?,
+        }
+
+        yield MoveProgress::Done(get_meta(&to).await?);
+    }
+}
+
+async fn remove_all(path: &path::Path) -> io::Result<()> {
+    match fs::symlink_metadata(path).await?.is_dir() {
+        true => fs::remove_dir_all(path).await,
+        false => fs::remove_file(path).await,
+    }
 }
 
 pub async fn delete_file(file: &path::Path) -> Result<()> {
@@ -168,11 +422,11 @@ pub async fn get_mime(file: &path::Path) -> Result<String> {
     task::spawn_blocking(move || {
         tree_magic_mini::from_filepath(file.as_path())
             .map(|s| s.to_string())
-            .with_context(|| {
-                format!(
+            .ok_or_else(|| {
+                Error::Other(anyhow::anyhow!(
                     "Could not get mime type for file '{}'",
                     file.to_string_lossy()
-                )
+                ))
             })
     })
     .await?
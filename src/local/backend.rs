@@ -0,0 +1,154 @@
+use std::path::Path;
+
+use async_trait::async_trait;
+use futures::{stream::BoxStream, StreamExt};
+
+use crate::*;
+
+/// The backend for files on the local file system, where ids are paths.
+pub struct Local;
+
+#[async_trait]
+impl Backend for Local {
+    async fn get(&self, id: &str) -> Result<File> {
+        local::get_meta(Path::new(id)).await
+    }
+
+    fn list<'a>(
+        &'a self,
+        dir_id: &'a str,
+        options: &'a ListOptions,
+    ) -> BoxStream<'a, Result<File>> {
+        local::list_meta(Path::new(dir_id), options.follow_links).boxed()
+    }
+
+    async fn find(&self, dir_id: &str, name: &str) -> Result<Option<File>> {
+        local::find(Path::new(dir_id), name).await
+    }
+
+    async fn read(&self, id: &str) -> Result<BoxedAsyncRead<'static>> {
+        local::read(Path::new(id)).await.map(|r| Box::pin(r) as _)
+    }
+
+    async fn read_range(&self, id: &str, offset: u64, len: u64) -> Result<BoxedAsyncRead<'static>> {
+        local::read_range(Path::new(id), offset, len)
+            .await
+            .map(|r| Box::pin(r) as _)
+    }
+
+    async fn read_seekable(&self, id: &str) -> Result<BoxedAsyncSeekRead<'static>> {
+        local::read(Path::new(id)).await.map(|r| Box::pin(r) as _)
+    }
+
+    async fn write(&self, id: &str) -> Result<BoxedAsyncWrite<'static>> {
+        local::write(Path::new(id)).await.map(|w| Box::pin(w) as _)
+    }
+
+    async fn write_with(&self, id: &str, mode: WriteMode) -> Result<BoxedAsyncWrite<'static>> {
+        local::write_with(Path::new(id), mode)
+            .await
+            .map(|w| Box::pin(w) as _)
+    }
+
+    async fn write_resumable(&self, id: &str, _session: Option<&str>) -> Result<ResumableWrite> {
+        let (offset, writer) = local::append(Path::new(id)).await?;
+
+        Ok(ResumableWrite {
+            writer: Box::pin(writer),
+            offset,
+            session: None,
+        })
+    }
+
+    async fn create(&self, file_type: &FileType, name: &str, parent_id: &str) -> Result<File> {
+        match file_type {
+            FileType::File => local::create_file(name, Path::new(parent_id)).await,
+            FileType::Dir => local::create_dir(name, Path::new(parent_id)).await,
+            FileType::Symlink { target } => {
+                local::create_symlink(name, target, Path::new(parent_id)).await
+            }
+            _ => Err(Error::unsupported(
+                "creating this file type is currently not supported",
+            )),
+        }
+    }
+
+    async fn copy(&self, id: &str, name: &str, dir_id: &str) -> Result<File> {
+        local::copy(Path::new(id), name, Path::new(dir_id)).await
+    }
+
+    fn copy_with_progress<'a>(
+        &'a self,
+        id: &'a str,
+        name: &'a str,
+        dir_id: &'a str,
+    ) -> BoxStream<'a, Result<CopyEvent>> {
+        local::copy_with_progress(Path::new(id), name, Path::new(dir_id)).boxed()
+    }
+
+    async fn checksum(&self, id: &str, algorithm: Checksum) -> Result<Option<String>> {
+        local::checksum(Path::new(id), algorithm).await.map(Some)
+    }
+
+    async fn hard_link(&self, id: &str, name: &str, dir_id: &str) -> Result<File> {
+        local::create_hard_link(Path::new(id), name, Path::new(dir_id)).await
+    }
+
+    async fn rename(&self, id: &str, new_name: &str) -> Result<()> {
+        local::rename(Path::new(id), new_name).await
+    }
+
+    async fn mv(&self, id: &str, dir_id: &str, name: &str) -> Result<File> {
+        local::mv(Path::new(id), Path::new(dir_id), name).await
+    }
+
+    fn mv_with_progress<'a>(
+        &'a self,
+        id: &'a str,
+        dir_id: &'a str,
+        name: &'a str,
+    ) -> BoxStream<'a, Result<MoveProgress>> {
+        local::mv_with_progress(Path::new(id), Path::new(dir_id), name).boxed()
+    }
+
+    #[cfg(unix)]
+    async fn trash(&self, id: &str) -> Result<TrashedFile> {
+        local::trash(Path::new(id)).await
+    }
+
+    #[cfg(unix)]
+    fn list_trash(&self) -> BoxStream<'_, Result<TrashedFile>> {
+        local::list_trash().boxed()
+    }
+
+    #[cfg(unix)]
+    async fn restore(&self, id: &str) -> Result<File> {
+        local::restore(Path::new(id)).await
+    }
+
+    #[cfg(unix)]
+    async fn empty_trash(&self) -> Result<()> {
+        local::empty_trash().await
+    }
+
+    #[cfg(target_os = "linux")]
+    fn watch<'a>(
+        &'a self,
+        dir_id: &'a str,
+        options: &'a WatchOptions,
+    ) -> BoxStream<'a, Result<WatchEvent>> {
+        local::watch(Path::new(dir_id), options.recursive).boxed()
+    }
+
+    async fn delete_file(&self, id: &str) -> Result<()> {
+        local::delete_file(Path::new(id)).await
+    }
+
+    async fn delete_dir(&self, id: &str) -> Result<()> {
+        local::delete_dir(Path::new(id)).await
+    }
+
+    async fn mime(&self, id: &str) -> Result<String> {
+        local::get_mime(Path::new(id)).await
+    }
+}
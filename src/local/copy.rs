@@ -0,0 +1,179 @@
+use std::path::{Path, PathBuf};
+
+use async_stream::try_stream;
+use futures::{Stream, TryStreamExt};
+use tokio::{fs, task};
+
+use super::{
+    api::{get_meta, list_meta},
+    sys,
+};
+use crate::*;
+
+/// Copies `from` and everything in it to `to`, keeping permissions, timestamps and, where the
+/// current user is allowed to, ownership.
+///
+/// `to` must not exist yet. Directories, regular files and symbolic links are copied, any other
+/// file type in the tree fails the copy before anything is written.
+pub fn copy_preserving<'a>(
+    from: &'a Path,
+    to: &'a Path,
+) -> impl Stream<Item = Result<CopyDirProgress>> + 'a {
+    try_stream! {
+        let entries = scan(from).await?;
+        let files = || entries.iter().filter(|(f, _)| f.file_type == FileType::File);
+
+        let files_total = files().count() as u64;
+        let bytes_total = files().map(|(f, _)| f.size).sum::<u64>();
+        let mut files_done = 0;
+        let mut bytes_done = 0;
+
+        for (f, rel) in entries.iter() {
+            let dest = join(to, rel);
+            let context = || format!("Could not copy '{}' to '{}'", f.id.1, dest.to_string_lossy());
+
+            match &f.file_type {
+                FileType::Dir => fs::create_dir(&dest).await.with_context(context)?,
+                FileType::Symlink { target } => sys::symlink(target, &dest).await.with_context(context)?,
+                _ => {
+                    let (src, dest) = (PathBuf::from(&f.id.1), dest.clone());
+                    let mut copier = task::spawn_blocking(move || sys::Copier::create_new(&src, &dest))
+                        .await?
+                        .with_context(context)?;
+
+                    loop {
+                        let (c, bytes) = task::spawn_blocking(move || {
+                            let bytes = copier.copy_chunk(CHUNK_SIZE);
+                            (copier, bytes)
+                        })
+                        .await?;
+                        copier = c;
+
+                        match bytes.with_context(context)? {
+                            0 => break,
+                            bytes => bytes_done += bytes,
+                        }
+
+                        yield CopyDirProgress {
+                            current: f.clone(),
+                            files_done,
+                            files_total,
+                            bytes_done,
+                            bytes_total,
+                        };
+                    }
+
+                    files_done += 1;
+
+                    yield CopyDirProgress {
+                        current: f.clone(),
+                        files_done,
+                        files_total,
+                        bytes_done,
+                        bytes_total,
+                    };
+                }
+            }
+        }
+
+        // children come after their parents in `entries`, so going backwards sets the timestamps
+        // of a directory only after nothing is written into it anymore
+        for (f, rel) in entries.iter().rev() {
+            let (src, dest) = (PathBuf::from(&f.id.1), join(to, rel));
+            let context = format!("Could not copy metadata of '{}'", f.id.1);
+
+            task::spawn_blocking(move || sys::copy_metadata(&src, &dest))
+                .await?
+                .with_context(|| context)?;
+        }
+    }
+}
+
+// The number of bytes copied between two progress events when the kernel copies the data.
+const CHUNK_SIZE: usize = 64 * 1024 * 1024;
+
+/// Copies the contents of the file `from` to the existing file `to`.
+///
+/// The data is copied by the kernel where it can, see [`sys::Copier`], in chunks so that every
+/// chunk can be reported.
+pub fn copy_file(from: &Path, to: File) -> impl Stream<Item = Result<CopyEvent>> + '_ {
+    try_stream! {
+        let dest = PathBuf::from(&to.id.1);
+        let context = || format!("Could not copy '{}' to '{}'", from.to_string_lossy(), to.id.1);
+
+        let (src_path, dest_path) = (from.to_path_buf(), dest.clone());
+        let mut copier = task::spawn_blocking(move || sys::Copier::new(&src_path, &dest_path))
+            .await?
+            .with_context(context)?;
+
+        let mut bytes_done = 0;
+        yield CopyEvent::Copying {
+            file: to.clone(),
+            bytes_done,
+        };
+
+        loop {
+            let (c, bytes) = task::spawn_blocking(move || {
+                let bytes = copier.copy_chunk(CHUNK_SIZE);
+                (copier, bytes)
+            })
+            .await?;
+            copier = c;
+
+            match bytes.with_context(context)? {
+                0 => break,
+                bytes => bytes_done += bytes,
+            }
+
+            yield CopyEvent::Copying {
+                file: to.clone(),
+                bytes_done,
+            };
+        }
+
+        yield CopyEvent::Done(get_meta(&dest).await?);
+    }
+}
+
+// `Path::join` adds a trailing separator when joining an empty path, which would make paths to
+// anything but directories invalid
+fn join(to: &Path, rel: &Path) -> PathBuf {
+    match rel.as_os_str().is_empty() {
+        true => to.to_path_buf(),
+        false => to.join(rel),
+    }
+}
+
+// Lists `root` and everything in it, parents before their children, along with their paths
+// relative to `root`.
+async fn scan(root: &Path) -> Result<Vec<(File, PathBuf)>> {
+    let mut entries = vec![(get_meta(root).await?, PathBuf::new())];
+    let mut i = 0;
+
+    while let Some((f, rel)) = entries.get(i) {
+        match f.file_type {
+            FileType::Dir => {
+                let rel = rel.clone();
+                let children = list_meta(Path::new(&f.id.1), false)
+                    .try_collect::<Vec<_>>()
+                    .await?;
+
+                for c in children {
+                    let path = rel.join(&c.name);
+                    entries.push((c, path));
+                }
+            }
+            FileType::File | FileType::Symlink { .. } => {}
+            _ => {
+                return Err(Error::unsupported(format!(
+                    "Copying special files like '{}' is not supported",
+                    f.id.1
+                )))
+            }
+        }
+
+        i += 1;
+    }
+
+    Ok(entries)
+}
@@ -1,3 +1,15 @@
 mod api;
+mod backend;
+mod copy;
+mod sys;
+#[cfg(unix)]
+mod trash;
+#[cfg(target_os = "linux")]
+mod watch;
 
 pub use api::*;
+pub use backend::Local;
+#[cfg(unix)]
+pub use trash::*;
+#[cfg(target_os = "linux")]
+pub use watch::*;
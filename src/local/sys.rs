@@ -0,0 +1,371 @@
+use std::{fs, io, path::Path};
+
+use crate::{FileType, Metadata};
+
+#[cfg(unix)]
+pub fn metadata(meta: &fs::Metadata) -> Metadata {
+    use std::os::unix::fs::MetadataExt;
+
+    Metadata {
+        mode: Some(meta.mode() & 0o7777),
+        uid: Some(meta.uid()),
+        gid: Some(meta.gid()),
+        owner: unix::user_name(meta.uid()),
+        group: unix::group_name(meta.gid()),
+        read_only: !unix::is_writable(meta),
+        ..Default::default()
+    }
+}
+
+#[cfg(not(unix))]
+pub fn metadata(meta: &fs::Metadata) -> Metadata {
+    Metadata {
+        read_only: meta.permissions().readonly(),
+        ..Default::default()
+    }
+}
+
+#[cfg(unix)]
+pub fn special_file_type(ft: &fs::FileType) -> FileType {
+    use std::os::unix::fs::FileTypeExt;
+
+    if ft.is_fifo() {
+        FileType::Fifo
+    } else if ft.is_socket() {
+        FileType::Socket
+    } else if ft.is_block_device() {
+        FileType::BlockDevice
+    } else if ft.is_char_device() {
+        FileType::CharDevice
+    } else {
+        FileType::Unknown
+    }
+}
+
+#[cfg(not(unix))]
+pub fn special_file_type(_ft: &fs::FileType) -> FileType {
+    FileType::Unknown
+}
+
+#[cfg(unix)]
+pub async fn symlink(target: &str, link: &Path) -> io::Result<()> {
+    tokio::fs::symlink(target, link).await
+}
+
+#[cfg(not(unix))]
+pub async fn symlink(_target: &str, _link: &Path) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "symbolic links are only supported on unix",
+    ))
+}
+
+/// Renames `from` to `to`, failing with `AlreadyExists` instead of replacing a file at `to`.
+///
+/// Linux does this atomically. Elsewhere, and on file systems that cannot, files are linked at the
+/// new name and unlinked at the old one, while directories are renamed after checking that `to`
+/// does not exist.
+pub fn rename_no_replace(from: &Path, to: &Path) -> io::Result<()> {
+    #[cfg(all(target_os = "linux", any(target_env = "gnu", target_env = "musl")))]
+    match linux::rename_no_replace(from, to) {
+        Err(e) if matches!(e.raw_os_error(), Some(libc::EINVAL | libc::ENOSYS)) => {}
+        r => return r,
+    }
+
+    if !fs::symlink_metadata(from)?.is_dir() {
+        match fs::hard_link(from, to) {
+            Ok(()) => return fs::remove_file(from),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => return Err(e),
+            // file systems without hard links
+            Err(_) => {}
+        }
+    }
+
+    if fs::symlink_metadata(to).is_ok() {
+        return Err(io::ErrorKind::AlreadyExists.into());
+    }
+    fs::rename(from, to)
+}
+
+/// Copies the ownership, permissions and timestamps of `from` to `to`.
+///
+/// Only root may give files away, so failing to change the owner is not an error.
+#[cfg(unix)]
+pub fn copy_metadata(from: &Path, to: &Path) -> io::Result<()> {
+    use std::os::unix::fs::{chown, lchown, MetadataExt};
+
+    let meta = fs::symlink_metadata(from)?;
+
+    // permissions and timestamps of links themselves are meaningless
+    if meta.is_symlink() {
+        let _ = lchown(to, Some(meta.uid()), Some(meta.gid()));
+        return Ok(());
+    }
+
+    // changing the owner may clear set-user-ID bits, so it comes before the permissions
+    let _ = chown(to, Some(meta.uid()), Some(meta.gid()));
+    copy_times(&meta, to)?;
+    fs::set_permissions(to, meta.permissions())
+}
+
+#[cfg(not(unix))]
+pub fn copy_metadata(from: &Path, to: &Path) -> io::Result<()> {
+    let meta = fs::symlink_metadata(from)?;
+
+    if meta.is_symlink() {
+        return Ok(());
+    }
+
+    copy_times(&meta, to)?;
+    fs::set_permissions(to, meta.permissions())
+}
+
+fn copy_times(meta: &fs::Metadata, to: &Path) -> io::Result<()> {
+    let times = fs::FileTimes::new()
+        .set_accessed(meta.accessed()?)
+        .set_modified(meta.modified()?);
+
+    fs::File::open(to)?.set_times(times)
+}
+
+/// Copies the contents of one file to another chunk by chunk.
+///
+/// On Linux the copy first tries to share the data with a reflink, which copy-on-write file systems
+/// like btrfs and XFS do instantly, then lets the kernel copy the data with `copy_file_range` or
+/// `sendfile`. Anything else, and file systems none of those work on, read and write the data
+/// through a buffer.
+pub struct Copier {
+    from: fs::File,
+    to: fs::File,
+    method: Method,
+}
+
+#[derive(PartialEq, Eq)]
+enum Method {
+    #[cfg(target_os = "linux")]
+    Reflink,
+    #[cfg(target_os = "linux")]
+    CopyFileRange,
+    #[cfg(target_os = "linux")]
+    Sendfile,
+    Buffered(Vec<u8>),
+    Done,
+}
+
+impl Copier {
+    /// Opens `from` and truncates `to` for copying.
+    pub fn new(from: &Path, to: &Path) -> io::Result<Self> {
+        Ok(Self::with_files(
+            fs::File::open(from)?,
+            fs::File::create(to)?,
+        ))
+    }
+
+    /// Opens `from` and creates `to` for copying, failing if `to` already exists.
+    pub fn create_new(from: &Path, to: &Path) -> io::Result<Self> {
+        let from = fs::File::open(from)?;
+        let to = fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(to)?;
+
+        Ok(Self::with_files(from, to))
+    }
+
+    fn with_files(from: fs::File, to: fs::File) -> Self {
+        #[cfg(target_os = "linux")]
+        let method = Method::Reflink;
+        #[cfg(not(target_os = "linux"))]
+        let method = Method::Buffered(vec![]);
+
+        Self { from, to, method }
+    }
+
+    /// Copies up to `size` bytes, returning how many were copied, `0` once everything was.
+    pub fn copy_chunk(&mut self, size: usize) -> io::Result<u64> {
+        loop {
+            let copied = match &mut self.method {
+                #[cfg(target_os = "linux")]
+                Method::Reflink => match linux::reflink(&self.from, &self.to)? {
+                    Some(bytes) => {
+                        self.method = Method::Done;
+                        Some(bytes)
+                    }
+                    None => None,
+                },
+                #[cfg(target_os = "linux")]
+                Method::CopyFileRange => linux::copy_file_range(&self.from, &self.to, size)?,
+                #[cfg(target_os = "linux")]
+                Method::Sendfile => linux::sendfile(&self.from, &self.to, size)?,
+                Method::Buffered(buf) => {
+                    use std::io::{Read, Write};
+
+                    buf.resize(size.min(5 * 1024 * 1024), 0);
+                    let bytes = self.from.read(buf)?;
+                    self.to.write_all(&buf[..bytes])?;
+                    Some(bytes as u64)
+                }
+                Method::Done => Some(0),
+            };
+
+            match copied {
+                Some(bytes) => return Ok(bytes),
+                // every method copies from and advances the offsets of both files, so the next one
+                // carries on where this one stopped
+                None => self.method = self.method.fallback(),
+            }
+        }
+    }
+}
+
+impl Method {
+    fn fallback(&self) -> Method {
+        match self {
+            #[cfg(target_os = "linux")]
+            Method::Reflink => Method::CopyFileRange,
+            #[cfg(target_os = "linux")]
+            Method::CopyFileRange => Method::Sendfile,
+            _ => Method::Buffered(vec![]),
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use std::{fs, io, os::fd::AsRawFd, path::Path, ptr};
+
+    #[cfg(any(target_env = "gnu", target_env = "musl"))]
+    pub fn rename_no_replace(from: &Path, to: &Path) -> io::Result<()> {
+        use std::{ffi::CString, os::unix::ffi::OsStrExt};
+
+        let from = CString::new(from.as_os_str().as_bytes())?;
+        let to = CString::new(to.as_os_str().as_bytes())?;
+        let (cwd, flags) = (libc::AT_FDCWD, libc::RENAME_NOREPLACE);
+
+        match unsafe { libc::renameat2(cwd, from.as_ptr(), cwd, to.as_ptr(), flags) } {
+            0 => Ok(()),
+            _ => Err(io::Error::last_os_error()),
+        }
+    }
+
+    /// Shares the data of `from` with `to`, returning the size of `from`, or `None` if the file
+    /// system cannot do that.
+    pub fn reflink(from: &fs::File, to: &fs::File) -> io::Result<Option<u64>> {
+        match unsafe { libc::ioctl(to.as_raw_fd(), libc::FICLONE, from.as_raw_fd()) } {
+            0 => Ok(Some(from.metadata()?.len())),
+            // reflinks are only ever an optimization, so whatever the reason they fail for, the
+            // data is copied instead
+            _ => Ok(None),
+        }
+    }
+
+    pub fn copy_file_range(from: &fs::File, to: &fs::File, size: usize) -> io::Result<Option<u64>> {
+        let (from, to) = (from.as_raw_fd(), to.as_raw_fd());
+        copied(unsafe {
+            libc::copy_file_range(from, ptr::null_mut(), to, ptr::null_mut(), size, 0)
+        })
+    }
+
+    pub fn sendfile(from: &fs::File, to: &fs::File, size: usize) -> io::Result<Option<u64>> {
+        copied(unsafe { libc::sendfile(to.as_raw_fd(), from.as_raw_fd(), ptr::null_mut(), size) })
+    }
+
+    // The number of bytes copied by a syscall returning `result`, or `None` if the syscall does
+    // not support copying between the two files.
+    fn copied(result: isize) -> io::Result<Option<u64>> {
+        if result >= 0 {
+            return Ok(Some(result as u64));
+        }
+
+        let e = io::Error::last_os_error();
+        match e.raw_os_error() {
+            Some(libc::ENOSYS | libc::EOPNOTSUPP | libc::EXDEV | libc::EINVAL) => Ok(None),
+            _ => Err(e),
+        }
+    }
+}
+
+#[cfg(unix)]
+mod unix {
+    use std::{
+        collections::HashMap, ffi::CStr, fs, mem, os::unix::fs::MetadataExt, ptr, sync::Mutex,
+    };
+
+    lazy_static::lazy_static! {
+        static ref USERS: Mutex<HashMap<u32, Option<String>>> = Mutex::new(HashMap::new());
+        static ref GROUPS: Mutex<HashMap<u32, Option<String>>> = Mutex::new(HashMap::new());
+        static ref EUID: u32 = unsafe { libc::geteuid() };
+        // the effective group and the supplementary groups of the process
+        static ref GROUP_IDS: Vec<u32> = group_ids();
+    }
+
+    pub fn user_name(uid: u32) -> Option<String> {
+        cached(&USERS, uid, |uid, buf| unsafe {
+            let mut pwd: libc::passwd = mem::zeroed();
+            let mut result = ptr::null_mut();
+            let r = libc::getpwuid_r(uid, &mut pwd, buf.as_mut_ptr(), buf.len(), &mut result);
+            (r, (!result.is_null()).then(|| CStr::from_ptr(pwd.pw_name)))
+        })
+    }
+
+    pub fn group_name(gid: u32) -> Option<String> {
+        cached(&GROUPS, gid, |gid, buf| unsafe {
+            let mut grp: libc::group = mem::zeroed();
+            let mut result = ptr::null_mut();
+            let r = libc::getgrgid_r(gid, &mut grp, buf.as_mut_ptr(), buf.len(), &mut result);
+            (r, (!result.is_null()).then(|| CStr::from_ptr(grp.gr_name)))
+        })
+    }
+
+    /// Whether the current user may write to a file with `meta`, going by its owner and
+    /// permission bits. ACLs and read-only mounts are not taken into account.
+    pub fn is_writable(meta: &fs::Metadata) -> bool {
+        let mode = meta.mode();
+
+        if *EUID == 0 {
+            true
+        } else if meta.uid() == *EUID {
+            mode & 0o200 != 0
+        } else if GROUP_IDS.contains(&meta.gid()) {
+            mode & 0o020 != 0
+        } else {
+            mode & 0o002 != 0
+        }
+    }
+
+    fn group_ids() -> Vec<u32> {
+        let mut ids = vec![unsafe { libc::getegid() }];
+
+        let n = unsafe { libc::getgroups(0, ptr::null_mut()) };
+        if n > 0 {
+            let mut groups = vec![0; n as usize];
+            let n = unsafe { libc::getgroups(n, groups.as_mut_ptr()) };
+            groups.truncate(n.max(0) as usize);
+            ids.extend(groups);
+        }
+
+        ids
+    }
+
+    // names are looked up for every listed file, so they are cached to avoid hitting NSS for
+    // each of them
+    fn cached<F>(cache: &Mutex<HashMap<u32, Option<String>>>, id: u32, lookup: F) -> Option<String>
+    where
+        F: for<'a> Fn(u32, &'a mut [libc::c_char]) -> (libc::c_int, Option<&'a CStr>),
+    {
+        if let Some(name) = cache.lock().unwrap().get(&id) {
+            return name.clone();
+        }
+
+        let mut buf = vec![0; 1024];
+        let name = loop {
+            match lookup(id, &mut buf) {
+                (libc::ERANGE, _) => buf.resize(buf.len() * 2, 0),
+                (_, name) => break name.map(|n| n.to_string_lossy().into_owned()),
+            }
+        };
+
+        cache.lock().unwrap().insert(id, name.clone());
+        name
+    }
+}
@@ -0,0 +1,577 @@
+// The FreeDesktop.org trash, see https://specifications.freedesktop.org/trash-spec/latest/
+
+use std::{
+    ffi::OsString,
+    io, mem,
+    os::unix::{
+        ffi::{OsStrExt, OsStringExt},
+        fs::{DirBuilderExt, MetadataExt},
+    },
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use async_stream::try_stream;
+use futures::Stream;
+use tokio::{fs, io::AsyncWriteExt, task};
+
+use super::{get_meta, sys};
+use crate::*;
+
+// A trash directory, with the trashed files in `files` and their `.trashinfo` files in `info`.
+struct TrashDir {
+    path: PathBuf,
+    // the top directory of the file system, which the paths in the trash are relative to, `None`
+    // for the home trash whose paths are absolute
+    top: Option<PathBuf>,
+}
+
+pub async fn trash(path: &Path) -> Result<TrashedFile> {
+    let context = || format!("Could not move '{}' to the trash", path.to_string_lossy());
+
+    let name = path
+        .file_name()
+        .ok_or_else(|| Error::unsupported("Trashing files without names is not supported!"))?;
+    // links in the path are resolved to find the file system the file is on
+    let parent = fs::canonicalize(path.parent().unwrap_or(Path::new(".")))
+        .await
+        .with_context(context)?;
+    let path = parent.join(name);
+
+    let meta = fs::symlink_metadata(&path).await.with_context(context)?;
+    let trash = trash_dir_for(&path, meta.dev())
+        .await
+        .with_context(context)?;
+
+    let (files, info) = (trash.path.join("files"), trash.path.join("info"));
+    for dir in [&files, &info] {
+        let created = std::fs::DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(dir);
+        match (created, &trash.top) {
+            (Ok(()), _) => {}
+            // like on a read-only mount, the home trash is no way out as it would mean copying
+            // the file to another file system
+            (Err(e), Some(top)) => {
+                return Err(Error::Unsupported {
+                    message: format!(
+                        "Could not move '{}' to the trash, there is no trash on '{}'",
+                        path.to_string_lossy(),
+                        top.to_string_lossy()
+                    ),
+                    source: Some(Box::new(e)),
+                })
+            }
+            (Err(e), None) => return Err(Error::from_io(e, context())),
+        }
+    }
+
+    let original = match &trash.top {
+        Some(top) => path.strip_prefix(top).unwrap_or(&path),
+        None => &path,
+    };
+    let trashed = SystemTime::now();
+    let content = format!(
+        "[Trash Info]\nPath={}\nDeletionDate={}\n",
+        encode_path(original),
+        format_local_time(trashed).unwrap_or_default()
+    );
+
+    // the info file is created first, which claims the name in the trash
+    let mut n = 1;
+    let (trashed_name, info_path) = loop {
+        let mut trashed_name = name.to_owned();
+        if n > 1 {
+            trashed_name.push(format!(".{n}"));
+        }
+        n += 1;
+
+        let mut info_name = trashed_name.clone();
+        info_name.push(".trashinfo");
+        let info_path = info.join(info_name);
+
+        if fs::symlink_metadata(files.join(&trashed_name))
+            .await
+            .is_ok()
+        {
+            continue;
+        }
+
+        let created = fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&info_path)
+            .await;
+        match created {
+            Ok(mut f) => {
+                // a file in the trash without its info could not be restored
+                let written = async {
+                    f.write_all(content.as_bytes()).await?;
+                    f.flush().await?;
+                    f.sync_all().await
+                };
+                if let Err(e) = written.await {
+                    let _ = fs::remove_file(&info_path).await;
+                    return Err(Error::from_io(e, context()));
+                }
+                break (trashed_name, info_path);
+            }
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {}
+            Err(e) => return Err(Error::from_io(e, context())),
+        }
+    };
+
+    let dest = files.join(trashed_name);
+    if let Err(e) = fs::rename(&path, &dest).await {
+        // without the file the info is useless
+        let _ = fs::remove_file(&info_path).await;
+        return Err(Error::from_io(e, context()));
+    }
+
+    Ok(TrashedFile {
+        file: get_meta(&dest).await?,
+        original_parent: Some(FileId(
+            FileSource::Local,
+            parent.to_string_lossy().to_string(),
+        )),
+        original_name: name.to_string_lossy().to_string(),
+        trashed: Some(trashed),
+    })
+}
+
+pub fn list_trash() -> impl Stream<Item = Result<TrashedFile>> {
+    try_stream! {
+        for trash in trash_dirs().await? {
+            let info = trash.path.join("info");
+            let context = || format!("Could not read the trash '{}'", trash.path.to_string_lossy());
+
+            let mut entries = match fs::read_dir(&info).await {
+                Ok(rd) => rd,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                Err(e) => Err(Error::from_io(e, context()))?,
+            };
+
+            while let Some(entry) = entries.next_entry().await.with_context(context)? {
+                let info_path = entry.path();
+                if info_path.extension() != Some("trashinfo".as_ref()) {
+                    continue;
+                }
+
+                let content = fs::read_to_string(&info_path).await.with_context(context)?;
+                let Some((original, trashed)) = parse_info(&content) else {
+                    continue;
+                };
+                let original = match &trash.top {
+                    Some(top) => top.join(original),
+                    None => original,
+                };
+
+                let name = info_path.file_stem().unwrap_or_default();
+                let file = match get_meta(&trash.path.join("files").join(name)).await {
+                    Ok(f) => f,
+                    // an info file left behind by an interrupted trash or restore
+                    Err(Error::NotFound { .. }) => continue,
+                    Err(e) => Err(e)?,
+                };
+
+                yield TrashedFile {
+                    file,
+                    original_parent: original
+                        .parent()
+                        .map(|p| FileId(FileSource::Local, p.to_string_lossy().to_string())),
+                    original_name: original
+                        .file_name()
+                        .unwrap_or_default()
+                        .to_string_lossy()
+                        .to_string(),
+                    trashed,
+                };
+            }
+        }
+    }
+}
+
+pub async fn restore(trashed: &Path) -> Result<File> {
+    let context = || format!("Could not restore '{}'", trashed.to_string_lossy());
+
+    let not_in_trash = || {
+        Error::not_found(format!(
+            "'{}' is not in the trash",
+            trashed.to_string_lossy()
+        ))
+    };
+
+    let (Some(name), Some(parent)) = (trashed.file_name(), trashed.parent()) else {
+        return Err(not_in_trash());
+    };
+    let parent = fs::canonicalize(parent).await.map_err(|_| not_in_trash())?;
+
+    let mut trash = None;
+    for dir in trash_dirs().await? {
+        if fs::canonicalize(dir.path.join("files")).await.ok() == Some(parent.clone()) {
+            trash = Some(dir);
+            break;
+        }
+    }
+    let trash = trash.ok_or_else(not_in_trash)?;
+
+    let mut info_name = name.to_owned();
+    info_name.push(".trashinfo");
+    let info_path = trash.path.join("info").join(info_name);
+
+    let content = fs::read_to_string(&info_path).await.with_context(context)?;
+    let (original, _) = parse_info(&content).ok_or_else(|| {
+        anyhow::anyhow!(
+            "The trash info '{}' is invalid",
+            info_path.to_string_lossy()
+        )
+    })?;
+    let original = match &trash.top {
+        Some(top) => top.join(original),
+        None => original,
+    };
+
+    if let Some(parent) = original.parent() {
+        fs::create_dir_all(parent).await.with_context(context)?;
+    }
+
+    // a file created at the original path in the meantime is never replaced
+    let (from, to) = (trashed.to_path_buf(), original.clone());
+    match task::spawn_blocking(move || sys::rename_no_replace(&from, &to)).await? {
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+            return Err(Error::already_exists(format!(
+                "Could not restore '{}', '{}' already exists",
+                trashed.to_string_lossy(),
+                original.to_string_lossy()
+            )))
+        }
+        r => r.with_context(context)?,
+    }
+    // the file is restored either way, and listing the trash skips info files without their file
+    let _ = fs::remove_file(&info_path).await;
+
+    get_meta(&original).await
+}
+
+pub async fn empty_trash() -> Result<()> {
+    for trash in trash_dirs().await? {
+        let context = || {
+            format!(
+                "Could not empty the trash '{}'",
+                trash.path.to_string_lossy()
+            )
+        };
+
+        // the files go first, so an interrupted emptying leaves no file without its info
+        for dir in ["files", "info"] {
+            let mut entries = match fs::read_dir(trash.path.join(dir)).await {
+                Ok(rd) => rd,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(Error::from_io(e, context())),
+            };
+
+            while let Some(entry) = entries.next_entry().await.with_context(context)? {
+                let removed = match entry.file_type().await.with_context(context)?.is_dir() {
+                    true => fs::remove_dir_all(entry.path()).await,
+                    false => fs::remove_file(entry.path()).await,
+                };
+                removed.with_context(context)?;
+            }
+        }
+
+        match fs::remove_file(trash.path.join("directorysizes")).await {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => {
+                return Err(Error::from_io(e, context()))
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+fn home_trash() -> io::Result<PathBuf> {
+    let data = std::env::var_os("XDG_DATA_HOME")
+        .filter(|d| !d.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|h| Path::new(&h).join(".local/share")))
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "the home directory is not set"))?;
+
+    Ok(data.join("Trash"))
+}
+
+// The trash for `path`, which is on the device `dev`.
+async fn trash_dir_for(path: &Path, dev: u64) -> io::Result<TrashDir> {
+    let home = home_trash()?;
+
+    // the home trash may not exist yet, but the file system it goes on does
+    let mut existing = home.as_path();
+    let home_dev = loop {
+        match fs::metadata(existing).await {
+            Ok(m) => break Some(m.dev()),
+            Err(_) => match existing.parent() {
+                Some(p) => existing = p,
+                None => break None,
+            },
+        }
+    };
+
+    if home_dev == Some(dev) {
+        return Ok(TrashDir {
+            path: home,
+            top: None,
+        });
+    }
+
+    let mut top = path;
+    while let Some(parent) = top.parent() {
+        match fs::metadata(parent).await {
+            Ok(m) if m.dev() == dev => top = parent,
+            _ => break,
+        }
+    }
+
+    let uid = unsafe { libc::getuid() };
+    let path = match admin_trash(top).await {
+        Some(admin) => admin.join(uid.to_string()),
+        None => top.join(format!(".Trash-{uid}")),
+    };
+
+    Ok(TrashDir {
+        path,
+        top: Some(top.to_path_buf()),
+    })
+}
+
+// The `.Trash` directory an administrator prepared in `top`, which has to have the sticky bit set
+// and must not be a link.
+async fn admin_trash(top: &Path) -> Option<PathBuf> {
+    let path = top.join(".Trash");
+    let meta = fs::symlink_metadata(&path).await.ok()?;
+
+    (meta.is_dir() && meta.mode() & 0o1000 != 0).then_some(path)
+}
+
+// The home trash and the trash directories of the current user on every mounted file system.
+async fn trash_dirs() -> Result<Vec<TrashDir>> {
+    let mut dirs = vec![TrashDir {
+        path: home_trash().with_context(|| "Could not find the trash".to_string())?,
+        top: None,
+    }];
+
+    // other systems than Linux have no list of mounts here, so only their home trash is used
+    let mounts = fs::read_to_string("/proc/self/mounts")
+        .await
+        .unwrap_or_default();
+    let uid = unsafe { libc::getuid() };
+
+    for top in mounts.lines().filter_map(|l| l.split(' ').nth(1)) {
+        let top = PathBuf::from(unescape_mount(top));
+
+        let mut candidates = vec![top.join(format!(".Trash-{uid}"))];
+        if let Some(admin) = admin_trash(&top).await {
+            candidates.push(admin.join(uid.to_string()));
+        }
+
+        for path in candidates {
+            if fs::metadata(&path).await.is_ok_and(|m| m.is_dir()) {
+                dirs.push(TrashDir {
+                    path,
+                    top: Some(top.clone()),
+                });
+            }
+        }
+    }
+
+    Ok(dirs)
+}
+
+// Undoes the octal escapes of spaces and other characters in a mount point.
+fn unescape_mount(s: &str) -> OsString {
+    let (bytes, mut out) = (s.as_bytes(), vec![]);
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let escaped = bytes.get(i + 1..i + 4).filter(|_| bytes[i] == b'\\');
+        match escaped.and_then(|o| u8::from_str_radix(std::str::from_utf8(o).ok()?, 8).ok()) {
+            Some(b) => {
+                out.push(b);
+                i += 4;
+            }
+            None => {
+                out.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+
+    OsString::from_vec(out)
+}
+
+// The original path and the deletion time in a `.trashinfo` file.
+fn parse_info(content: &str) -> Option<(PathBuf, Option<SystemTime>)> {
+    let mut lines = content.lines().map(str::trim);
+    if lines.next()? != "[Trash Info]" {
+        return None;
+    }
+
+    let (mut path, mut trashed) = (None, None);
+    for line in lines {
+        match line.split_once('=') {
+            Some(("Path", p)) => path = decode_path(p),
+            Some(("DeletionDate", d)) => trashed = parse_local_time(d),
+            _ => {}
+        }
+    }
+
+    Some((path?, trashed))
+}
+
+// Percent-encodes `path` like a URL path.
+fn encode_path(path: &Path) -> String {
+    path.as_os_str()
+        .as_bytes()
+        .iter()
+        .map(|&b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+                (b as char).to_string()
+            }
+            b => format!("%{b:02X}"),
+        })
+        .collect()
+}
+
+fn decode_path(s: &str) -> Option<PathBuf> {
+    let (bytes, mut out) = (s.as_bytes(), vec![]);
+    let mut i = 0;
+
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' => {
+                let hex = std::str::from_utf8(bytes.get(i + 1..i + 3)?).ok()?;
+                out.push(u8::from_str_radix(hex, 16).ok()?);
+                i += 3;
+            }
+            b => {
+                out.push(b);
+                i += 1;
+            }
+        }
+    }
+
+    Some(OsString::from_vec(out).into())
+}
+
+// Formats `t` as `YYYY-MM-DDThh:mm:ss` in local time.
+fn format_local_time(t: SystemTime) -> Option<String> {
+    let secs = t.duration_since(UNIX_EPOCH).ok()?.as_secs() as libc::time_t;
+
+    let tm = unsafe {
+        let mut tm: libc::tm = mem::zeroed();
+        if libc::localtime_r(&secs, &mut tm).is_null() {
+            return None;
+        }
+        tm
+    };
+
+    Some(format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+        tm.tm_year + 1900,
+        tm.tm_mon + 1,
+        tm.tm_mday,
+        tm.tm_hour,
+        tm.tm_min,
+        tm.tm_sec
+    ))
+}
+
+fn parse_local_time(s: &str) -> Option<SystemTime> {
+    let (date, time) = s.split_once('T')?;
+    let parts = |s: &str, sep| {
+        s.split(sep)
+            .map(|p| p.parse::<libc::c_int>().ok())
+            .collect::<Option<Vec<_>>>()
+    };
+    let (date, time) = (parts(date, '-')?, parts(time, ':')?);
+    let ([year, month, day], [hour, min, sec]) =
+        (date[..].try_into().ok()?, time[..].try_into().ok()?);
+
+    let secs = unsafe {
+        let mut tm: libc::tm = mem::zeroed();
+        tm.tm_year = year - 1900;
+        tm.tm_mon = month - 1;
+        tm.tm_mday = day;
+        tm.tm_hour = hour;
+        tm.tm_min = min;
+        tm.tm_sec = sec;
+        tm.tm_isdst = -1;
+        libc::mktime(&mut tm)
+    };
+
+    Some(UNIX_EPOCH + Duration::from_secs(u64::try_from(secs).ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use futures::TryStreamExt;
+
+    use crate::{testing::TempDir, Error};
+
+    #[test]
+    fn test_info() {
+        let path = Path::new("/home/me/my file%.txt");
+        let encoded = super::encode_path(path);
+        assert_eq!(encoded, "/home/me/my%20file%25.txt");
+
+        let info = format!("[Trash Info]\nPath={encoded}\nDeletionDate=2024-02-29T13:05:09\n");
+        let (decoded, trashed) = super::parse_info(&info).unwrap();
+        assert_eq!(decoded, path);
+        assert_eq!(
+            super::format_local_time(trashed.unwrap()).unwrap(),
+            "2024-02-29T13:05:09"
+        );
+
+        assert!(super::parse_info("Path=/a").is_none());
+        assert_eq!(super::unescape_mount(r"/mnt/my\040disk"), "/mnt/my disk");
+    }
+
+    #[tokio::test]
+    async fn test_trash_and_restore() {
+        let tmp = TempDir::new("trash");
+        // the home trash is in the temporary directory, on the file system of the trashed files,
+        // and no other test uses it
+        std::env::set_var("XDG_DATA_HOME", tmp.path("data"));
+        let (path, info) = (tmp.path("dir/a.txt"), tmp.path("data/Trash/info"));
+
+        tmp.write("dir/a.txt", "first");
+        let first = super::trash(&path).await.unwrap();
+        tmp.write("dir/a.txt", "second");
+        let second = super::trash(&path).await.unwrap();
+        assert!(!path.exists());
+        assert_eq!(first.original_name, "a.txt");
+        assert_eq!(first.original_parent, Some(tmp.id("dir")));
+        assert!(second.file.id.1.ends_with("/files/a.txt.2"));
+        assert!(info.join("a.txt.2.trashinfo").exists());
+
+        let listed = super::list_trash().try_collect::<Vec<_>>().await.unwrap();
+        for trashed in [&first, &second] {
+            assert!(listed.iter().any(|t| t.file.id == trashed.file.id));
+        }
+
+        // a file at the original path is not replaced
+        tmp.write("dir/a.txt", "new");
+        let e = super::restore(Path::new(&first.file.id.1))
+            .await
+            .unwrap_err();
+        assert!(matches!(e, Error::AlreadyExists { .. }), "{e}");
+        assert_eq!(tmp.read("dir/a.txt").as_deref(), Some("new"));
+
+        std::fs::remove_file(&path).unwrap();
+        super::restore(Path::new(&first.file.id.1)).await.unwrap();
+        assert_eq!(tmp.read("dir/a.txt").as_deref(), Some("first"));
+        assert!(!info.join("a.txt.trashinfo").exists());
+    }
+}
@@ -0,0 +1,365 @@
+use std::{
+    collections::HashMap,
+    ffi::{CString, OsStr},
+    io,
+    os::{
+        fd::{AsRawFd, FromRawFd, OwnedFd},
+        unix::ffi::OsStrExt,
+    },
+    path::{Path, PathBuf},
+    ptr,
+    time::Duration,
+};
+
+use async_stream::try_stream;
+use futures::Stream;
+use tokio::{fs, io::unix::AsyncFd};
+
+use super::get_meta;
+use crate::*;
+
+const MASK: u32 = libc::IN_CREATE
+    | libc::IN_CLOSE_WRITE
+    | libc::IN_ATTRIB
+    | libc::IN_DELETE
+    | libc::IN_MOVED_FROM
+    | libc::IN_MOVED_TO
+    | libc::IN_DELETE_SELF
+    | libc::IN_MOVE_SELF
+    | libc::IN_ONLYDIR;
+
+// How long to wait for the second half of a move, which only comes if the file stayed in a watched
+// directory.
+const MOVE_WAIT: Duration = Duration::from_millis(50);
+
+pub fn watch(dir: &Path, recursive: bool) -> impl Stream<Item = Result<WatchEvent>> + '_ {
+    try_stream! {
+        let context = || format!("Could not watch '{}'", dir.to_string_lossy());
+
+        let mut inotify = Inotify::new(recursive).with_context(context)?;
+        inotify.add_tree(dir).await.with_context(context)?;
+
+        let mut buf = vec![0; 64 * 1024];
+        // the first half of a move, with its cookie and whether a directory was moved
+        let mut moved_from: Option<(u32, PathBuf, bool)> = None;
+
+        loop {
+            let n = match moved_from {
+                None => inotify.read(&mut buf).await.with_context(context)?,
+                Some(_) => match tokio::time::timeout(MOVE_WAIT, inotify.read(&mut buf)).await {
+                    Ok(n) => n.with_context(context)?,
+                    Err(_) => 0,
+                },
+            };
+
+            if n == 0 {
+                // the file was moved out of the watched directories
+                if let Some((_, from, is_dir)) = moved_from.take() {
+                    if is_dir {
+                        inotify.remove_tree(&from);
+                    }
+                    yield WatchEvent::Deleted(local_id(&from));
+                }
+                continue;
+            }
+
+            for e in parse(&buf[..n]) {
+                if e.mask & libc::IN_Q_OVERFLOW != 0 {
+                    Err(anyhow::anyhow!(
+                        "Too many changes in '{}', some of them were lost",
+                        dir.to_string_lossy()
+                    ))?;
+                }
+
+                let Some(parent) = inotify.dirs.get(&e.wd).cloned() else {
+                    continue;
+                };
+                let is_dir = e.mask & libc::IN_ISDIR != 0;
+
+                if e.mask & libc::IN_IGNORED != 0 {
+                    inotify.dirs.remove(&e.wd);
+                }
+                if e.name.as_os_str().is_empty() {
+                    // the directory itself changed, which its parent already reports
+                    if e.mask & (libc::IN_DELETE_SELF | libc::IN_MOVE_SELF) != 0 && parent == dir {
+                        yield WatchEvent::Deleted(local_id(dir));
+                        return;
+                    }
+                    continue;
+                }
+                let path = parent.join(&e.name);
+
+                if let Some((cookie, from, from_dir)) = moved_from.take() {
+                    if e.mask & libc::IN_MOVED_TO != 0 && e.cookie == cookie {
+                        if from_dir {
+                            inotify.rename_tree(&from, &path);
+                        }
+                        if let Some(to) = meta(&path).await? {
+                            yield WatchEvent::Renamed { from: local_id(&from), to };
+                        }
+                        continue;
+                    }
+
+                    if from_dir {
+                        inotify.remove_tree(&from);
+                    }
+                    yield WatchEvent::Deleted(local_id(&from));
+                }
+
+                if e.mask & libc::IN_MOVED_FROM != 0 {
+                    moved_from = Some((e.cookie, path, is_dir));
+                } else if e.mask & (libc::IN_CREATE | libc::IN_MOVED_TO) != 0 {
+                    if let Some(f) = meta(&path).await? {
+                        yield WatchEvent::Created(f);
+                    }
+
+                    // what was put into the directory before it was watched is reported too, unless
+                    // it is gone again already
+                    if is_dir && recursive {
+                        let found = match inotify.add_tree(&path).await {
+                            Ok(found) => found,
+                            Err(e) if is_gone(&e) => vec![],
+                            Err(e) => Err(Error::from_io(e, context()))?,
+                        };
+                        for p in found {
+                            if let Some(f) = meta(&p).await? {
+                                yield WatchEvent::Created(f);
+                            }
+                        }
+                    }
+                } else if e.mask & (libc::IN_CLOSE_WRITE | libc::IN_ATTRIB) != 0 {
+                    if let Some(f) = meta(&path).await? {
+                        yield WatchEvent::Modified(f);
+                    }
+                } else if e.mask & libc::IN_DELETE != 0 {
+                    yield WatchEvent::Deleted(local_id(&path));
+                }
+            }
+        }
+    }
+}
+
+struct Inotify {
+    fd: AsyncFd<OwnedFd>,
+    recursive: bool,
+    // the watched directories by their watch descriptors
+    dirs: HashMap<i32, PathBuf>,
+}
+
+struct Event {
+    wd: i32,
+    mask: u32,
+    cookie: u32,
+    name: PathBuf,
+}
+
+impl Inotify {
+    fn new(recursive: bool) -> io::Result<Self> {
+        let fd = unsafe { libc::inotify_init1(libc::IN_NONBLOCK | libc::IN_CLOEXEC) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        // the descriptor is owned, so it stays open as long as it is registered
+        let fd = unsafe { AsyncFd::register(OwnedFd::from_raw_fd(fd))? };
+
+        Ok(Self {
+            fd,
+            recursive,
+            dirs: HashMap::new(),
+        })
+    }
+
+    fn add(&mut self, dir: &Path) -> io::Result<()> {
+        let path = CString::new(dir.as_os_str().as_bytes())?;
+        let wd = unsafe { libc::inotify_add_watch(self.fd.as_raw_fd(), path.as_ptr(), MASK) };
+        if wd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        self.dirs.insert(wd, dir.to_path_buf());
+        Ok(())
+    }
+
+    // Watches `dir` and for recursive watches its subdirectories, returning everything in them.
+    //
+    // Subdirectories that are gone or cannot be read are left out.
+    async fn add_tree(&mut self, dir: &Path) -> io::Result<Vec<PathBuf>> {
+        self.add(dir)?;
+        if !self.recursive {
+            return Ok(vec![]);
+        }
+
+        let (mut found, mut dirs) = (vec![], vec![dir.to_path_buf()]);
+        while let Some(dir) = dirs.pop() {
+            let Ok(mut entries) = fs::read_dir(&dir).await else {
+                continue;
+            };
+
+            while let Ok(Some(entry)) = entries.next_entry().await {
+                let path = entry.path();
+                if entry.file_type().await.is_ok_and(|t| t.is_dir()) {
+                    match self.add(&path) {
+                        Ok(()) => dirs.push(path.clone()),
+                        Err(e) if is_gone(&e) => {}
+                        Err(e) => return Err(e),
+                    }
+                }
+                found.push(path);
+            }
+        }
+
+        Ok(found)
+    }
+
+    // Stops watching `dir` and the directories in it.
+    fn remove_tree(&mut self, dir: &Path) {
+        let fd = self.fd.as_raw_fd();
+        self.dirs.retain(|&wd, path| {
+            let inside = path.starts_with(dir);
+            if inside {
+                unsafe { libc::inotify_rm_watch(fd, wd) };
+            }
+            !inside
+        });
+    }
+
+    // Updates the paths of the watched directories in `from`, which was moved to `to`.
+    fn rename_tree(&mut self, from: &Path, to: &Path) {
+        for path in self.dirs.values_mut() {
+            if let Ok(rest) = path.strip_prefix(from) {
+                *path = to.join(rest);
+            }
+        }
+    }
+
+    async fn read(&self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let mut guard = self.fd.readable().await?;
+
+            let read = guard.try_io(|fd| {
+                let n =
+                    unsafe { libc::read(fd.as_raw_fd(), buf.as_mut_ptr() as *mut _, buf.len()) };
+                match n < 0 {
+                    true => Err(io::Error::last_os_error()),
+                    false => Ok(n as usize),
+                }
+            });
+
+            if let Ok(n) = read {
+                return n;
+            }
+        }
+    }
+}
+
+fn parse(buf: &[u8]) -> Vec<Event> {
+    const HEADER: usize = std::mem::size_of::<libc::inotify_event>();
+
+    let (mut events, mut offset) = (vec![], 0);
+    while offset + HEADER <= buf.len() {
+        let e =
+            unsafe { ptr::read_unaligned(buf[offset..].as_ptr() as *const libc::inotify_event) };
+
+        // names are padded with zeros
+        let name = &buf[offset + HEADER..offset + HEADER + e.len as usize];
+        let len = name.iter().position(|&b| b == 0).unwrap_or(name.len());
+
+        events.push(Event {
+            wd: e.wd,
+            mask: e.mask,
+            cookie: e.cookie,
+            name: PathBuf::from(OsStr::from_bytes(&name[..len])),
+        });
+        offset += HEADER + e.len as usize;
+    }
+
+    events
+}
+
+fn is_gone(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        io::ErrorKind::NotFound | io::ErrorKind::PermissionDenied
+    ) || e.raw_os_error() == Some(libc::ENOTDIR)
+}
+
+// The file at `path`, unless it is gone again already.
+async fn meta(path: &Path) -> Result<Option<File>> {
+    match get_meta(path).await {
+        Ok(f) => Ok(Some(f)),
+        Err(Error::NotFound { .. }) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+fn local_id(path: &Path) -> FileId {
+    FileId(FileSource::Local, path.to_string_lossy().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::StreamExt;
+
+    use super::*;
+    use crate::testing::TempDir;
+
+    // The events until nothing happens for a while, as `kind path` with paths relative to `root`.
+    async fn events<S>(s: &mut S, root: &Path) -> Vec<String>
+    where
+        S: Stream<Item = Result<WatchEvent>> + Unpin,
+    {
+        let path = |id: &FileId| {
+            let path = Path::new(&id.1).strip_prefix(root).unwrap();
+            path.to_string_lossy().to_string()
+        };
+
+        let mut events = vec![];
+        while let Ok(Some(e)) = tokio::time::timeout(Duration::from_millis(200), s.next()).await {
+            events.push(match e.unwrap() {
+                WatchEvent::Created(f) => format!("created {}", path(&f.id)),
+                WatchEvent::Modified(f) => format!("modified {}", path(&f.id)),
+                WatchEvent::Deleted(id) => format!("deleted {}", path(&id)),
+                WatchEvent::Renamed { from, to } => {
+                    format!("renamed {} {}", path(&from), path(&to.id))
+                }
+            });
+        }
+        events
+    }
+
+    #[tokio::test]
+    async fn test_watch() {
+        let (tmp, outside) = (TempDir::new("watch"), TempDir::new("watch-outside"));
+        let root = tmp.path("");
+        let s = watch(&root, true);
+        futures::pin_mut!(s);
+        // the watch starts once the stream is polled
+        assert!(events(&mut s, &root).await.is_empty());
+
+        tmp.write("a.txt", "a");
+        assert_eq!(
+            events(&mut s, &root).await,
+            ["created a.txt", "modified a.txt"]
+        );
+
+        std::fs::rename(tmp.path("a.txt"), tmp.path("b.txt")).unwrap();
+        assert_eq!(events(&mut s, &root).await, ["renamed a.txt b.txt"]);
+
+        std::fs::create_dir(tmp.path("d")).unwrap();
+        assert_eq!(events(&mut s, &root).await, ["created d"]);
+        tmp.write("d/x", "x");
+        assert_eq!(events(&mut s, &root).await, ["created d/x", "modified d/x"]);
+        std::fs::remove_file(tmp.path("d/x")).unwrap();
+        assert_eq!(events(&mut s, &root).await, ["deleted d/x"]);
+
+        // moving a file out of the watched directory deletes it there
+        std::fs::rename(tmp.path("b.txt"), outside.path("b.txt")).unwrap();
+        assert_eq!(events(&mut s, &root).await, ["deleted b.txt"]);
+
+        // deleting the watched directory itself, whose relative path is empty, ends the watch
+        std::fs::remove_dir_all(&root).unwrap();
+        assert_eq!(events(&mut s, &root).await, ["deleted d", "deleted "]);
+        assert!(s.next().await.is_none());
+    }
+}
@@ -0,0 +1,147 @@
+use std::{
+    cmp::Ordering,
+    collections::VecDeque,
+    iter::Peekable,
+    str::Chars,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use crate::*;
+
+// How many sorted listings are kept for paging through them, and for how long.
+const KEPT_LISTINGS: usize = 16;
+const LISTING_TTL: Duration = Duration::from_secs(300);
+
+lazy_static::lazy_static! {
+    static ref LISTINGS: Mutex<Listings> = Mutex::new(Listings {
+        next_token: 0,
+        kept: VecDeque::new(),
+    });
+}
+
+// Sorted listings by the tokens in the cursors of their pages, so that paging through a directory
+// lists and sorts it only once.
+struct Listings {
+    next_token: u64,
+    // the oldest first
+    kept: VecDeque<(u64, Instant, Arc<Vec<File>>)>,
+}
+
+/// Keeps `files` for the following pages of a listing, returning the token to find them by.
+pub fn keep_listing(files: Arc<Vec<File>>) -> u64 {
+    let mut listings = LISTINGS.lock().unwrap();
+
+    let token = listings.next_token;
+    listings.next_token += 1;
+
+    if listings.kept.len() == KEPT_LISTINGS {
+        listings.kept.pop_front();
+    }
+    listings.kept.push_back((token, Instant::now(), files));
+
+    token
+}
+
+/// The listing kept under `token`, unless it was dropped or is too old to still be used.
+pub fn kept_listing(token: u64) -> Option<Arc<Vec<File>>> {
+    let mut listings = LISTINGS.lock().unwrap();
+    listings
+        .kept
+        .retain(|(_, kept, _)| kept.elapsed() < LISTING_TTL);
+
+    listings
+        .kept
+        .iter()
+        .find(|(t, _, _)| *t == token)
+        .map(|(_, _, files)| files.clone())
+}
+
+/// Drops the listing kept under `token` once its last page was listed.
+pub fn drop_listing(token: u64) {
+    LISTINGS
+        .lock()
+        .unwrap()
+        .kept
+        .retain(|(t, _, _)| *t != token);
+}
+
+/// Sorts `files` by `key`, breaking ties by name.
+pub fn sort(files: &mut [File], key: SortKey, direction: SortDirection) {
+    let by_name = |a: &File, b: &File| natural_cmp(&a.name, &b.name);
+
+    match key {
+        SortKey::Unsorted => return,
+        SortKey::Name => files.sort_by(by_name),
+        SortKey::Size => files.sort_by(|a, b| a.size.cmp(&b.size).then_with(|| by_name(a, b))),
+        SortKey::Modified => files.sort_by(|a, b| {
+            a.metadata
+                .modified
+                .cmp(&b.metadata.modified)
+                .then_with(|| by_name(a, b))
+        }),
+        SortKey::DirsFirst => files.sort_by(|a, b| {
+            let is_file = |f: &File| f.file_type != FileType::Dir;
+            is_file(a).cmp(&is_file(b)).then_with(|| by_name(a, b))
+        }),
+    }
+
+    if direction == SortDirection::Descending {
+        files.reverse();
+    }
+}
+
+/// Compares names the way people read them: ignoring case, and with runs of digits compared by
+/// their value, so `file9` comes before `file10`.
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (mut x, mut y) = (a.chars().peekable(), b.chars().peekable());
+
+    loop {
+        let ordering = match (x.peek().copied(), y.peek().copied()) {
+            (None, None) => return a.cmp(b),
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(c), Some(d)) if c.is_ascii_digit() && d.is_ascii_digit() => {
+                number(&mut x).cmp(&number(&mut y))
+            }
+            (Some(c), Some(d)) => {
+                x.next();
+                y.next();
+                c.to_lowercase().cmp(d.to_lowercase())
+            }
+        };
+
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+}
+
+// Takes a run of digits from `chars`, returned so that comparing two of them compares their value.
+fn number(chars: &mut Peekable<Chars>) -> (usize, String) {
+    let mut digits = String::new();
+    while let Some(c) = chars.next_if(char::is_ascii_digit) {
+        if !(digits.is_empty() && c == '0') {
+            digits.push(c);
+        }
+    }
+
+    (digits.len(), digits)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cmp::Ordering::*;
+
+    use super::natural_cmp;
+
+    #[test]
+    fn test_natural_cmp() {
+        assert_eq!(natural_cmp("file9", "file10"), Less);
+        assert_eq!(natural_cmp("File2", "file10"), Less);
+        assert_eq!(natural_cmp("a007", "a7"), Less);
+        assert_eq!(natural_cmp("a7b", "a7a"), Greater);
+        assert_eq!(natural_cmp("abc", "ab"), Greater);
+        assert_eq!(natural_cmp("x", "x"), Equal);
+    }
+}
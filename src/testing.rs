@@ -0,0 +1,49 @@
+//! Helpers for the tests that work on local files.
+
+use std::path::PathBuf;
+
+use crate::{FileId, FileSource};
+
+/// A directory in the temporary directory, removed with everything in it when dropped.
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    pub fn new(name: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!("files-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        TempDir(dir)
+    }
+
+    /// The path of `path` in this directory, which is the directory itself if `path` is empty.
+    pub fn path(&self, path: &str) -> PathBuf {
+        match path {
+            "" => self.0.clone(),
+            _ => self.0.join(path),
+        }
+    }
+
+    pub fn id(&self, path: &str) -> FileId {
+        FileId(
+            FileSource::Local,
+            self.path(path).to_string_lossy().to_string(),
+        )
+    }
+
+    /// Writes `contents` to `path`, creating its parents.
+    pub fn write(&self, path: &str, contents: &str) {
+        let path = self.path(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, contents).unwrap();
+    }
+
+    pub fn read(&self, path: &str) -> Option<String> {
+        std::fs::read_to_string(self.path(path)).ok()
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
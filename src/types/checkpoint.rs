@@ -0,0 +1,43 @@
+#[cfg(feature = "serde")]
+use std::{io, path::Path};
+
+use crate::*;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Where a copy stopped, kept in [`CopyOptions::checkpoint`] while the copy runs so it can be
+/// continued with [`resume_copy`](crate::resume_copy) after the process was restarted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Checkpoint {
+    pub source: FileId,
+    pub dest: FileId,
+    /// The handle of the file source to continue writing `dest`, like a Google Drive upload URL.
+    pub session: Option<String>,
+    /// The number of bytes copied when the checkpoint was written.
+    pub bytes_done: u64,
+}
+
+#[cfg(feature = "serde")]
+impl Checkpoint {
+    /// Reads a checkpoint written by a copy.
+    pub async fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let context = || format!("Could not read checkpoint '{}'", path.to_string_lossy());
+
+        let json = tokio::fs::read(path).await.with_context(context)?;
+        serde_json::from_slice(&json)
+            .map_err(io::Error::from)
+            .with_context(context)
+    }
+
+    pub(crate) async fn save(&self, path: &Path) -> Result<()> {
+        let context = || format!("Could not write checkpoint '{}'", path.to_string_lossy());
+
+        let json = serde_json::to_vec(self)
+            .map_err(io::Error::from)
+            .with_context(context)?;
+        local::write_atomic(path, &json).await.with_context(context)
+    }
+}
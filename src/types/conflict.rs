@@ -0,0 +1,69 @@
+use std::{fmt, future::Future, sync::Arc};
+
+use futures::{future::BoxFuture, FutureExt};
+
+use crate::*;
+
+type AskFn = dyn Fn(Conflict) -> BoxFuture<'static, ConflictPolicy> + Send + Sync;
+
+/// What to do when a file is created, copied, moved or renamed to a name that is already taken in
+/// its directory.
+///
+/// The policies comparing the two files treat a newly created file as an empty file modified just
+/// now, and a file without a modification time as newer.
+#[derive(Clone, Default)]
+pub enum ConflictPolicy {
+    /// Fail with [`Error::AlreadyExists`](crate::Error::AlreadyExists).
+    #[default]
+    Fail,
+    /// Replace the existing file. The new file is written under a temporary name like
+    /// `.name.partial` and only takes the place of the existing one once it is complete.
+    /// Directories are only replaced if they are empty, otherwise this fails like
+    /// [`Fail`](ConflictPolicy::Fail).
+    Overwrite,
+    /// Leave the existing file as it is and do nothing.
+    Skip,
+    /// Use the first free name of the form `name (1).ext`, `name (2).ext` and so on.
+    KeepBoth,
+    /// Overwrite the existing file if it was modified before the incoming one, skip it otherwise.
+    OverwriteIfNewer,
+    /// Overwrite the existing file if its size differs from the incoming one, skip it otherwise.
+    OverwriteIfDifferentSize,
+    /// Ask a callback which of the other policies to apply to each conflict, e.g. by prompting the
+    /// user. Answering with `Ask` again fails like [`Fail`](ConflictPolicy::Fail).
+    Ask(Arc<AskFn>),
+}
+
+impl ConflictPolicy {
+    /// An [`Ask`](ConflictPolicy::Ask) policy calling `ask` for every conflict.
+    pub fn ask<F, Fut>(ask: F) -> Self
+    where
+        F: Fn(Conflict) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ConflictPolicy> + Send + 'static,
+    {
+        Self::Ask(Arc::new(move |c| ask(c).boxed()))
+    }
+}
+
+impl fmt::Debug for ConflictPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Fail => write!(f, "Fail"),
+            Self::Overwrite => write!(f, "Overwrite"),
+            Self::Skip => write!(f, "Skip"),
+            Self::KeepBoth => write!(f, "KeepBoth"),
+            Self::OverwriteIfNewer => write!(f, "OverwriteIfNewer"),
+            Self::OverwriteIfDifferentSize => write!(f, "OverwriteIfDifferentSize"),
+            Self::Ask(_) => write!(f, "Ask(..)"),
+        }
+    }
+}
+
+/// A name conflict passed to [`ConflictPolicy::Ask`].
+#[derive(Debug, Clone)]
+pub struct Conflict {
+    /// The file that already has the name.
+    pub existing: File,
+    /// The file being copied, moved or renamed, `None` when creating a new file.
+    pub incoming: Option<File>,
+}
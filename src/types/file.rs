@@ -1,4 +1,3 @@
-use anyhow::Result;
 use futures::Stream;
 
 use crate::*;
@@ -14,6 +13,7 @@ pub struct File {
     pub size: u64,
     pub id: FileId,
     pub parent_id: Option<FileId>,
+    pub metadata: Metadata,
 }
 
 impl File {
@@ -26,19 +26,20 @@ impl File {
     }
 
     pub async fn rename(&mut self, new_name: &str) -> Result<()> {
-        api::rename(&self.id, new_name).await?;
-
-        self.name = new_name.into();
+        *self = api::rename_with(&self.id, new_name, &ConflictPolicy::Fail).await?;
         Ok(())
     }
 
     pub async fn move_to_dir(&mut self, dir_id: &FileId) -> Result<()> {
-        api::move_to_dir(&self.id, dir_id).await?;
-
-        self.parent_id = Some(dir_id.to_owned());
+        *self = api::move_file(&self.id, dir_id, MoveOptions::default()).await?;
         Ok(())
     }
 
+    pub async fn trash(self) -> Result<TrashedFile> {
+        api::trash(&self.id).await
+    }
+
+    /// Deletes the file for good, see [`trash`](File::trash) to move it to the trash instead.
     pub async fn delete(self) -> Result<()> {
         match self.file_type {
             FileType::Dir => api::delete_dir(&self.id).await,
@@ -46,6 +47,13 @@ impl File {
         }
     }
 
+    pub fn delete_recursive(
+        &self,
+        options: DeleteOptions,
+    ) -> impl Stream<Item = Result<DeleteProgress>> + '_ {
+        api::delete_recursive(&self.id, options)
+    }
+
     pub async fn mime(&self) -> Result<String> {
         api::mime(&self.id).await
     }
@@ -54,7 +62,15 @@ impl File {
         api::list(&self.id)
     }
 
-    pub fn copy_to_dir<'a>(&'a self, dir_id: &'a FileId) -> impl Stream<Item = Result<u64>> + 'a {
+    pub fn copy_to_dir<'a>(&'a self, dir_id: &'a FileId) -> impl Stream<Item = CopyProgress> + 'a {
         api::copy_to_dir(&self.id, &self.name, dir_id)
     }
+
+    pub fn copy_dir<'a>(
+        &'a self,
+        dir_id: &'a FileId,
+        options: CopyOptions,
+    ) -> impl Stream<Item = Result<CopyDirProgress>> + 'a {
+        api::copy_dir(&self.id, &self.name, dir_id, options)
+    }
 }
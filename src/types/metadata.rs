@@ -0,0 +1,29 @@
+use std::time::SystemTime;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Metadata of a [`File`](crate::File) beyond its name, type and size.
+///
+/// Fields a file source has no notion of are left as `None`, e.g. mode bits on Google Drive.
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Metadata {
+    pub modified: Option<SystemTime>,
+    pub created: Option<SystemTime>,
+    pub accessed: Option<SystemTime>,
+    /// The Unix permission bits, e.g. `0o644`.
+    pub mode: Option<u32>,
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+    /// The name of the owning user, or the display name of the owner on Google Drive.
+    pub owner: Option<String>,
+    pub group: Option<String>,
+    pub hidden: bool,
+    /// Whether the current user is not allowed to modify the file.
+    pub read_only: bool,
+    /// The hex encoded MD5 checksum the file source keeps for the file, local files have none.
+    pub md5: Option<String>,
+    /// The hex encoded SHA-256 checksum the file source keeps for the file, local files have none.
+    pub sha256: Option<String>,
+}
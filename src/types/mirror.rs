@@ -0,0 +1,96 @@
+use std::fmt;
+
+/// How [`mirror`](crate::mirror) tells whether a file in the destination differs from the one in
+/// the source.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MirrorCompare {
+    /// The files differ if their sizes do, or if the source was modified after the destination.
+    ///
+    /// Copies between file sources get the time they were made as their modification time, so a
+    /// destination modified later than its source is taken to be a copy of it.
+    #[default]
+    SizeAndModified,
+    /// The files differ if their checksums do. Files that have no checksum kept, like local ones,
+    /// are read to hash them.
+    Checksum,
+}
+
+/// What [`mirror`](crate::mirror) does with the files in the destination that are not in the
+/// source.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum ExtraneousPolicy {
+    /// Leaves them as they are.
+    #[default]
+    Keep,
+    Delete,
+    /// Moves them into a folder named after the time of the mirror, like `2024-05-01_12-30-00`,
+    /// in this directory of the destination. The time is in UTC, and a folder already taken by an
+    /// earlier mirror gets a number, like `2024-05-01_12-30-00 (1)`.
+    ///
+    /// The directory is given relative to the destination with `/` between names, like
+    /// `.archive`, and is itself left out of the mirror. It cannot be the destination itself.
+    Archive(String),
+}
+
+/// A step of a [`MirrorPlan`]. Paths are relative to the destination, with `/` between names.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MirrorAction {
+    CreateDir {
+        path: String,
+    },
+    /// Copies the file at `path` in the source to the destination, replacing the file there.
+    Copy {
+        path: String,
+    },
+    /// Deletes the file at `path`, or the directory with everything in it.
+    Delete {
+        path: String,
+    },
+    /// Moves the file or directory at `path` to `to` in the archive folder.
+    Archive {
+        path: String,
+        to: String,
+    },
+}
+
+impl fmt::Display for MirrorAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::CreateDir { path } => write!(f, "create dir  {path}"),
+            Self::Copy { path } => write!(f, "copy        {path}"),
+            Self::Delete { path } => write!(f, "delete      {path}"),
+            Self::Archive { path, to } => write!(f, "archive     {path} -> {to}"),
+        }
+    }
+}
+
+/// What [`mirror`](crate::mirror) does to make the destination look like the source, see
+/// [`mirror_plan`](crate::mirror_plan). Printing a plan lists its actions one per line.
+#[derive(Debug, Clone, Default)]
+pub struct MirrorPlan {
+    /// The actions in the order they are carried out: extraneous files are archived or deleted
+    /// first, then directories are created and files copied.
+    pub actions: Vec<MirrorAction>,
+    /// The paths that are a file on one side and a directory on the other but are kept by
+    /// [`ExtraneousPolicy::Keep`], or that are in the archive folder, which are left as they are.
+    pub skipped: Vec<String>,
+}
+
+impl MirrorPlan {
+    pub fn is_empty(&self) -> bool {
+        self.actions.is_empty()
+    }
+}
+
+impl fmt::Display for MirrorPlan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for a in &self.actions {
+            writeln!(f, "{a}")?;
+        }
+        for path in &self.skipped {
+            writeln!(f, "skip        {path}")?;
+        }
+
+        Ok(())
+    }
+}
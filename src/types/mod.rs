@@ -1,32 +1,105 @@
+mod checkpoint;
+mod conflict;
 mod file;
+mod metadata;
+mod mirror;
+mod options;
+mod page;
+mod progress;
+mod query;
+mod sync;
+mod trash;
+mod usage;
+mod walk;
+mod watch;
+
+pub use checkpoint::Checkpoint;
+pub use conflict::{Conflict, ConflictPolicy};
 pub use file::File;
+pub use metadata::Metadata;
+pub use mirror::{ExtraneousPolicy, MirrorAction, MirrorCompare, MirrorPlan};
+pub use options::*;
+pub use page::{Cursor, Page};
+pub use progress::*;
+pub use query::Query;
+pub(crate) use sync::Stamp;
+pub use sync::{Side, SyncAction, SyncConflictPolicy, SyncPlan, SyncState};
+pub use trash::TrashedFile;
+pub use usage::{DiskUsage, UsageTree};
+pub use walk::WalkEntry;
+pub use watch::WatchEvent;
 
 use std::pin::Pin;
 
-use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::io::{AsyncRead, AsyncSeek, AsyncWrite};
 
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
 pub type BoxedAsyncRead<'a> = Pin<Box<dyn AsyncRead + Send + 'a>>;
 pub type BoxedAsyncWrite<'a> = Pin<Box<dyn AsyncWrite + Send + 'a>>;
+pub type BoxedAsyncSeekRead<'a> = Pin<Box<dyn AsyncSeekRead + Send + 'a>>;
+
+/// A reader that can seek, see [`read_seekable`](crate::read_seekable).
+pub trait AsyncSeekRead: AsyncRead + AsyncSeek {}
+
+impl<T: AsyncRead + AsyncSeek + ?Sized> AsyncSeekRead for T {}
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum FileType {
     File,
     Dir,
+    /// A symbolic link, `target` is the path it points to as stored in the link.
+    Symlink {
+        target: String,
+    },
+    Fifo,
+    Socket,
+    BlockDevice,
+    CharDevice,
     Unknown,
 }
 
-#[derive(Debug, Clone)]
+/// A hash algorithm used to verify copies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Checksum {
+    Md5,
+    Sha256,
+}
+
+/// How [`write_with`](crate::write_with) treats the existing contents of a file.
+///
+/// Google Drive cannot change parts of a file, so there every mode but `Truncate` and `CreateNew`
+/// uploads the whole file again: the kept contents are downloaded and uploaded along with what is
+/// written. Appending to or patching large Drive files is as slow as copying them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum WriteMode {
+    /// Replace the contents of the file.
+    #[default]
+    Truncate,
+    /// Write after the end of the file.
+    Append,
+    /// Fail with [`Error::AlreadyExists`](crate::Error::AlreadyExists) unless the file is new. For
+    /// local files that means it must not exist yet, Drive files must still be empty.
+    CreateNew,
+    /// Write over the contents of the file starting at the offset, keeping everything else. Writing
+    /// past the end of the file fills the gap with zeros.
+    WriteAt(u64),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct FileId(pub FileSource, pub String);
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum FileSource {
     Local,
     #[cfg(feature = "google_drive")]
     GoogleDrive(String),
+    /// A backend registered at runtime with [`register_backend`](crate::register_backend).
+    Custom(String),
 }
@@ -0,0 +1,282 @@
+use std::{fmt, path::PathBuf, sync::Arc, time::Duration};
+
+use tokio::sync::watch;
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    Checksum, ConflictPolicy, Cursor, ExtraneousPolicy, MirrorCompare, SyncConflictPolicy,
+    WalkEntry,
+};
+
+/// Options for [`list_with`](crate::list_with) and [`list_page`](crate::list_page).
+#[derive(Debug, Clone)]
+pub struct ListOptions {
+    /// Describe the targets of symbolic links instead of the links themselves.
+    ///
+    /// Links whose target does not exist are still listed as links.
+    pub follow_links: bool,
+    /// Ends the listing with [`Error::Cancelled`](crate::Error::Cancelled) once cancelled.
+    pub cancel: CancellationToken,
+    /// Sorting local files means reading the whole directory before the first entry is returned,
+    /// Google Drive sorts on the server.
+    pub sort: SortKey,
+    pub direction: SortDirection,
+    /// List hidden files, whose names start with a dot. Defaults to `true`.
+    pub include_hidden: bool,
+    /// The largest number of files in a page, Google Drive returns at most 1000 at a time.
+    pub page_size: Option<usize>,
+    /// Where to continue a paged listing, the [`Page::next`](crate::Page::next) of the previous
+    /// page. Without one the listing starts at the beginning.
+    pub cursor: Option<Cursor>,
+}
+
+impl Default for ListOptions {
+    fn default() -> Self {
+        Self {
+            follow_links: false,
+            cancel: CancellationToken::new(),
+            sort: SortKey::default(),
+            direction: SortDirection::default(),
+            include_hidden: true,
+            page_size: None,
+            cursor: None,
+        }
+    }
+}
+
+/// What to sort listings by, ties are sorted by name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SortKey {
+    /// The order the file source lists files in.
+    #[default]
+    Unsorted,
+    /// The name, ignoring case and comparing runs of digits by their value, so `file9` comes
+    /// before `file10`.
+    Name,
+    /// The size. Google Drive sorts by the storage the files use instead, which differs from
+    /// [`File::size`](crate::File::size) for Google Docs and for files owned by others, so such
+    /// files may be out of order there.
+    Size,
+    /// The modification time, files without one come first.
+    Modified,
+    /// Directories before everything else.
+    DirsFirst,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SortDirection {
+    #[default]
+    Ascending,
+    Descending,
+}
+
+type PruneFn = dyn Fn(&WalkEntry) -> bool + Send + Sync;
+
+/// Options for [`walk`](crate::walk).
+#[derive(Clone)]
+pub struct WalkOptions {
+    /// How deep to walk, `Some(1)` only yields the entries of the walked directory itself.
+    pub max_depth: Option<usize>,
+    pub order: WalkOrder,
+    /// Called for every directory, returning `true` yields the directory but skips its entries.
+    pub prune: Option<Arc<PruneFn>>,
+    /// Glob patterns like `*.rs` or `src/**/*.rs`, only matching entries are yielded. All
+    /// directories are still walked into, whether they match or not.
+    ///
+    /// Patterns with a `/` are matched against the path of an entry, others against its name.
+    pub include: Vec<String>,
+    /// Glob patterns like `include`, matching entries are neither yielded nor walked into.
+    pub exclude: Vec<String>,
+    /// Walk into symbolic links to directories. Directories that were already walked, like the
+    /// targets of links pointing back up the tree, are yielded but not walked again.
+    pub follow_links: bool,
+    /// The number of directories listed at the same time.
+    pub concurrency: usize,
+}
+
+impl Default for WalkOptions {
+    fn default() -> Self {
+        Self {
+            max_depth: None,
+            order: WalkOrder::default(),
+            prune: None,
+            include: vec![],
+            exclude: vec![],
+            follow_links: false,
+            concurrency: 4,
+        }
+    }
+}
+
+impl fmt::Debug for WalkOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WalkOptions")
+            .field("max_depth", &self.max_depth)
+            .field("order", &self.order)
+            .field("prune", &self.prune.as_ref().map(|_| ".."))
+            .field("include", &self.include)
+            .field("exclude", &self.exclude)
+            .field("follow_links", &self.follow_links)
+            .field("concurrency", &self.concurrency)
+            .finish()
+    }
+}
+
+/// The order [`walk`](crate::walk) yields entries in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WalkOrder {
+    /// All entries of a depth before any deeper ones.
+    #[default]
+    BreadthFirst,
+    /// Every directory directly followed by its entries.
+    DepthFirst,
+}
+
+/// Options for [`watch`](crate::watch).
+#[derive(Debug, Clone)]
+pub struct WatchOptions {
+    /// Also report changes in subdirectories, at any depth.
+    pub recursive: bool,
+    /// How often Google Drive is asked for changes. Defaults to 30 seconds.
+    pub poll_interval: Duration,
+    /// A file to keep the Google Drive page token and the files known to be in the watched
+    /// folder in, so the changes made while nothing was watching are reported by the next watch of
+    /// the same folder that uses the same file.
+    ///
+    /// The file is written once the events of a poll were taken from the stream.
+    pub cursor_file: Option<PathBuf>,
+    /// Ends the watch once cancelled.
+    pub cancel: CancellationToken,
+}
+
+impl Default for WatchOptions {
+    fn default() -> Self {
+        Self {
+            recursive: false,
+            poll_interval: Duration::from_secs(30),
+            cursor_file: None,
+            cancel: CancellationToken::new(),
+        }
+    }
+}
+
+/// Options for [`sync`](crate::sync) and [`sync_plan`](crate::sync_plan).
+#[derive(Debug, Clone, Default)]
+pub struct SyncOptions {
+    /// What to do with files that were changed on both sides.
+    pub conflict: SyncConflictPolicy,
+    /// Stops the sync between two actions once cancelled, the sync then fails with
+    /// [`Error::Cancelled`](crate::Error::Cancelled). The actions carried out so far are kept in
+    /// the [`SyncState`](crate::SyncState).
+    pub cancel: CancellationToken,
+}
+
+/// Options for [`mirror`](crate::mirror) and [`mirror_plan`](crate::mirror_plan).
+#[derive(Debug, Clone, Default)]
+pub struct MirrorOptions {
+    /// How to tell whether a file needs to be copied again.
+    pub compare: MirrorCompare,
+    /// What to do with files in the destination that are not in the source.
+    pub extraneous: ExtraneousPolicy,
+    /// Stops the mirror between two actions once cancelled, the mirror then fails with
+    /// [`Error::Cancelled`](crate::Error::Cancelled).
+    pub cancel: CancellationToken,
+}
+
+/// Options for [`delete_recursive`](crate::delete_recursive).
+#[derive(Debug, Clone, Default)]
+pub struct DeleteOptions {
+    /// Report entries that could not be deleted as progress events and carry on with the rest,
+    /// instead of ending the stream with the first error.
+    pub continue_on_error: bool,
+}
+
+/// Options for [`copy_dir`](crate::copy_dir) and [`copy_to_dir_with`](crate::copy_to_dir_with).
+#[derive(Debug, Clone)]
+pub struct CopyOptions {
+    /// The number of files copied at the same time, only used when copying directories.
+    pub concurrency: usize,
+    /// Stops the copy once cancelled and deletes everything it created so far, the copy then
+    /// fails with [`Error::Cancelled`](crate::Error::Cancelled).
+    pub cancel: CancellationToken,
+    /// Holds the copy between two buffers while paused.
+    pub pause: PauseHandle,
+    /// Verifies every copied file by comparing checksums, failing the copy with
+    /// [`Error::ChecksumMismatch`](crate::Error::ChecksumMismatch) if they differ.
+    ///
+    /// The copied bytes are hashed as they are copied and compared with the checksums Google Drive
+    /// keeps for the source and the copy. Only if neither has one, like between two local
+    /// directories, the copy is read a second time to hash it. A copy the file source made itself,
+    /// which only happens if it keeps a checksum of the source, and a resumed copy are compared by
+    /// the checksums of the source and the whole copy instead. A copy that fails the check is left
+    /// in place.
+    pub verify: Option<Checksum>,
+    /// A file to keep a [`Checkpoint`](crate::Checkpoint) of the copy in, so it can be continued
+    /// with [`resume_copy`](crate::resume_copy) if the process stops before the copy is done.
+    ///
+    /// The file is removed once the copy completed or was cancelled, and written at most once a
+    /// second while it runs. Only used when copying a single file, which is then always copied
+    /// through this process, and only to file sources that support
+    /// [`write_resumable`](crate::Backend::write_resumable). Keeping
+    /// a checkpoint needs the `serde` feature, without it such copies fail with
+    /// [`Error::Unsupported`](crate::Error::Unsupported).
+    pub checkpoint: Option<PathBuf>,
+    /// What to do if the destination already has a file with the name of the copy. For
+    /// directories it only applies to the copied directory itself, which is copied into an empty
+    /// directory.
+    pub conflict: ConflictPolicy,
+}
+
+impl Default for CopyOptions {
+    fn default() -> Self {
+        Self {
+            concurrency: 4,
+            cancel: CancellationToken::new(),
+            pause: PauseHandle::default(),
+            verify: None,
+            checkpoint: None,
+            conflict: ConflictPolicy::Fail,
+        }
+    }
+}
+
+/// Options for [`move_with`](crate::move_with).
+#[derive(Debug, Clone, Default)]
+pub struct MoveOptions {
+    /// What to do if the destination already has a file with the name of the moved file.
+    pub conflict: ConflictPolicy,
+}
+
+/// Pauses and resumes the copies it was passed to in their [`CopyOptions`].
+///
+/// Clones of a handle control the same copies. A paused copy keeps its destination and, for Google
+/// Drive, its upload session open, so resuming it carries on where it stopped.
+#[derive(Debug, Clone)]
+pub struct PauseHandle(Arc<watch::Sender<bool>>);
+
+impl PauseHandle {
+    pub fn pause(&self) {
+        self.0.send_replace(true);
+    }
+
+    pub fn resume(&self) {
+        self.0.send_replace(false);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        *self.0.borrow()
+    }
+
+    // Waits until the handle is not paused.
+    pub(crate) async fn wait(&self) {
+        let mut paused = self.0.subscribe();
+        // the sender lives in `self`, so this never fails
+        let _ = paused.wait_for(|paused| !paused).await;
+    }
+}
+
+impl Default for PauseHandle {
+    fn default() -> Self {
+        Self(Arc::new(watch::channel(false).0))
+    }
+}
@@ -0,0 +1,40 @@
+use std::fmt;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::*;
+
+/// A page of a directory listing, see [`list_page`](crate::list_page).
+#[derive(Debug, Clone)]
+pub struct Page {
+    pub files: Vec<File>,
+    /// Where the next page starts, `None` if this is the last page.
+    pub next: Option<Cursor>,
+}
+
+/// Where a paged listing continues, passed back in [`ListOptions::cursor`].
+///
+/// A cursor is opaque, but can be kept as a string and turned back into a cursor with `From`. It
+/// is only valid for the directory and options it was returned for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Cursor(String);
+
+impl Cursor {
+    pub(crate) fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<String> for Cursor {
+    fn from(s: String) -> Self {
+        Self(s)
+    }
+}
+
+impl fmt::Display for Cursor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
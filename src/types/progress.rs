@@ -0,0 +1,129 @@
+use std::time::Duration;
+
+use crate::*;
+
+/// An entry processed by [`delete_recursive`](crate::delete_recursive).
+#[derive(Debug)]
+pub struct DeleteProgress {
+    pub file: File,
+    /// The path of the entry, relative to the parent of the deleted directory.
+    pub path: String,
+    /// The number of bytes freed by deleting the entry, `0` for directories and failed entries.
+    pub bytes_freed: u64,
+    pub error: Option<Error>,
+}
+
+/// An event of a [`du`](crate::du) operation.
+#[derive(Debug, Clone)]
+pub enum DuProgress {
+    /// A directory was found, `usage` is what was counted so far.
+    Counting { path: String, usage: DiskUsage },
+    /// Everything was counted, this is always the last event.
+    Done(DiskUsage),
+}
+
+/// An event of a [`sync`](crate::sync) operation.
+#[derive(Debug, Clone)]
+pub enum SyncProgress {
+    /// The plan was made, this is always the first event.
+    Planned(SyncPlan),
+    /// An action of the plan was carried out.
+    Applied(SyncAction),
+    /// An action of the plan was left out, because what it would change was changed since the
+    /// plan was made, or an earlier action it needs was left out. The next sync looks at it again.
+    Skipped(SyncAction),
+}
+
+/// An event of a [`mirror`](crate::mirror) operation.
+#[derive(Debug, Clone)]
+pub enum MirrorProgress {
+    /// The plan was made, this is always the first event.
+    Planned(MirrorPlan),
+    /// An action of the plan was carried out.
+    Applied(MirrorAction),
+}
+
+/// An event of a [`copy_to_dir`](crate::copy_to_dir) operation.
+///
+/// A copy emits `Started` once the destination file was created, followed by any number of
+/// `Progress` events, and always ends with either `Completed` or `Failed`.
+#[derive(Debug)]
+pub enum CopyProgress {
+    Started(CopyStatus),
+    Progress(CopyStatus),
+    /// The copy finished, `file` in the status is the destination as it is after the copy.
+    Completed(CopyStatus),
+    /// The copy failed. If it already emitted `Started` the partially written destination is
+    /// deleted, unless it is kept to be resumed from [`CopyOptions::checkpoint`] or the copy
+    /// failed its [`CopyOptions::verify`] check.
+    Failed(Error),
+    /// The [`ConflictPolicy`] left the existing file with the name of the copy as it is, nothing
+    /// was copied. This is the only event of the copy.
+    Skipped(File),
+}
+
+/// The state of a single file copy.
+#[derive(Debug, Clone)]
+pub struct CopyStatus {
+    /// The destination file.
+    pub file: File,
+    pub bytes_done: u64,
+    /// The size of the source file.
+    pub bytes_total: u64,
+    /// The throughput in bytes per second since the previous event.
+    pub speed: f64,
+    /// The throughput in bytes per second since the copy started.
+    pub average_speed: f64,
+    /// The time since the copy started.
+    pub elapsed: Duration,
+}
+
+impl CopyStatus {
+    /// The estimated time until the copy is done, based on the average throughput so far.
+    pub fn eta(&self) -> Option<Duration> {
+        if self.average_speed <= 0.0 {
+            return None;
+        }
+
+        let remaining = self.bytes_total.saturating_sub(self.bytes_done);
+        Some(Duration::from_secs_f64(
+            remaining as f64 / self.average_speed,
+        ))
+    }
+}
+
+/// An event of [`Backend::copy_with_progress`](crate::Backend::copy_with_progress).
+#[derive(Debug, Clone)]
+pub enum CopyEvent {
+    /// `file` is the copy, of which the first `bytes_done` bytes were copied so far.
+    Copying { file: File, bytes_done: u64 },
+    /// The copy is complete, this is always the last event.
+    Done(File),
+}
+
+/// The progress of a [`copy_dir`](crate::copy_dir) operation.
+#[derive(Debug, Clone)]
+pub struct CopyDirProgress {
+    /// The source file that was being copied when this event was emitted.
+    pub current: File,
+    pub files_done: u64,
+    pub files_total: u64,
+    pub bytes_done: u64,
+    pub bytes_total: u64,
+}
+
+/// The progress of a [`move_with_progress`](crate::move_with_progress) operation.
+#[derive(Debug, Clone)]
+pub enum MoveProgress {
+    /// Bytes were copied to the destination, only emitted when moving across file sources.
+    Copying(CopyDirProgress),
+    /// The copy is being compared with the source.
+    Verifying,
+    /// The source is being deleted after its copy was verified.
+    Deleting,
+    /// The file was moved, this is always the last event.
+    Done(File),
+    /// The [`ConflictPolicy`] left the existing file with the name of the file in the destination
+    /// as it is, nothing was moved. This is the only event of the move.
+    Skipped(File),
+}
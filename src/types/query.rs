@@ -0,0 +1,47 @@
+use std::time::SystemTime;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::*;
+
+/// What [`search`](crate::search) looks for, a file has to match every field that is set.
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Query {
+    /// A part of the name, ignoring case.
+    ///
+    /// Google Drive only finds names with a word starting with it, e.g. `port` finds `port.txt`
+    /// and `my port.txt`, but not `report.txt`.
+    pub name_contains: Option<String>,
+    /// The mime type, e.g. `image/png`.
+    pub mime_type: Option<String>,
+    pub file_type: Option<FileType>,
+    /// The smallest size in bytes.
+    pub min_size: Option<u64>,
+    /// The largest size in bytes.
+    pub max_size: Option<u64>,
+    /// Files without a modification time never match either of the two.
+    pub modified_after: Option<SystemTime>,
+    pub modified_before: Option<SystemTime>,
+}
+
+impl Query {
+    // Whether `file` matches everything but the mime type, which is not part of a `File`.
+    pub(crate) fn matches_metadata(&self, file: &File) -> bool {
+        let modified = file.metadata.modified;
+
+        self.name_contains
+            .as_ref()
+            .is_none_or(|n| file.name.to_lowercase().contains(&n.to_lowercase()))
+            && self.file_type.as_ref().is_none_or(|t| *t == file.file_type)
+            && self.min_size.is_none_or(|s| file.size >= s)
+            && self.max_size.is_none_or(|s| file.size <= s)
+            && self
+                .modified_after
+                .is_none_or(|t| modified.is_some_and(|m| m > t))
+            && self
+                .modified_before
+                .is_none_or(|t| modified.is_some_and(|m| m < t))
+    }
+}
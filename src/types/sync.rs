@@ -0,0 +1,251 @@
+use std::{collections::BTreeMap, fmt, time::SystemTime};
+
+#[cfg(feature = "serde")]
+use std::{io, path::Path};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::*;
+
+/// One of the two directories kept in sync by [`sync`](crate::sync).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Side {
+    Left,
+    Right,
+}
+
+impl Side {
+    pub fn other(self) -> Self {
+        match self {
+            Self::Left => Self::Right,
+            Self::Right => Self::Left,
+        }
+    }
+
+    pub(crate) fn index(self) -> usize {
+        self as usize
+    }
+}
+
+impl fmt::Display for Side {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Left => f.pad("left"),
+            Self::Right => f.pad("right"),
+        }
+    }
+}
+
+/// How [`sync`](crate::sync) resolves a file that was changed on both sides since the last sync,
+/// and does not have the same contents on both.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SyncConflictPolicy {
+    /// Keeps the right version under a new name like `notes (1).txt` next to the left one, on both
+    /// sides.
+    #[default]
+    KeepBoth,
+    /// Copies the version modified last over the other, the left one if they were modified at the
+    /// same time.
+    NewerWins,
+    /// Copies the version of this side over the other.
+    Prefer(Side),
+}
+
+/// A step of a [`SyncPlan`]. Paths are relative to the synced directories, with `/` between
+/// names.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SyncAction {
+    CreateDir {
+        side: Side,
+        path: String,
+    },
+    /// Copies the file at `path` on `from` to the other side, replacing the file there.
+    Copy {
+        from: Side,
+        path: String,
+    },
+    /// Renames or moves the file at `from` on `side` to `to`.
+    Rename {
+        side: Side,
+        from: String,
+        to: String,
+    },
+    /// Deletes the file at `path` on `side`, or the directory with everything in it.
+    Delete {
+        side: Side,
+        path: String,
+    },
+}
+
+impl fmt::Display for SyncAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::CreateDir { side, path } => write!(f, "create dir {side:<5}  {path}"),
+            Self::Copy { from, path } => {
+                write!(f, "copy       {from:<5}  {path} -> {}", from.other())
+            }
+            Self::Rename { side, from, to } => write!(f, "rename     {side:<5}  {from} -> {to}"),
+            Self::Delete { side, path } => write!(f, "delete     {side:<5}  {path}"),
+        }
+    }
+}
+
+/// What [`sync`](crate::sync) does to bring two directories in sync, see
+/// [`sync_plan`](crate::sync_plan). Printing a plan lists its actions one per line.
+#[derive(Debug, Clone, Default)]
+pub struct SyncPlan {
+    /// The actions in the order they are carried out: directories are created first, then files
+    /// are renamed, copied and deleted.
+    pub actions: Vec<SyncAction>,
+    /// The paths changed on both sides, whose actions follow [`SyncOptions::conflict`].
+    pub conflicts: Vec<String>,
+    /// The paths that are a file on one side and a directory on the other, which are left as they
+    /// are.
+    pub skipped: Vec<String>,
+}
+
+impl SyncPlan {
+    pub fn is_empty(&self) -> bool {
+        self.actions.is_empty()
+    }
+}
+
+impl fmt::Display for SyncPlan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for a in &self.actions {
+            writeln!(f, "{a}")?;
+        }
+        for path in &self.conflicts {
+            writeln!(f, "conflict          {path}")?;
+        }
+        for path in &self.skipped {
+            writeln!(f, "skip              {path}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The two directories as they were after the last sync, which tells [`sync`](crate::sync) what
+/// changed on which side since then.
+///
+/// A sync updates the state it is given, which is then kept for the next sync of the same
+/// directories. With the `serde` feature it can be saved to a file and loaded from it.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SyncState {
+    pub(crate) left: FileId,
+    pub(crate) right: FileId,
+    /// The entries that were in sync, by their paths.
+    pub(crate) entries: BTreeMap<String, [Stamp; 2]>,
+}
+
+/// What is compared to tell whether a file changed since the last sync.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub(crate) struct Stamp {
+    pub id: String,
+    pub is_dir: bool,
+    pub size: u64,
+    pub modified: Option<SystemTime>,
+    pub md5: Option<String>,
+}
+
+impl Stamp {
+    /// Whether `file` is still what the stamp was taken of.
+    pub fn matches(&self, file: &File) -> bool {
+        let is_dir = file.file_type == FileType::Dir;
+        if self.is_dir || is_dir {
+            return self.is_dir == is_dir && self.id == file.id.1;
+        }
+
+        self.id == file.id.1
+            && self.size == file.size
+            && self.modified == file.metadata.modified
+            && (self.md5.is_none() || file.metadata.md5.is_none() || self.md5 == file.metadata.md5)
+    }
+}
+
+impl From<&File> for Stamp {
+    fn from(file: &File) -> Self {
+        Self {
+            id: file.id.1.clone(),
+            is_dir: file.file_type == FileType::Dir,
+            size: file.size,
+            modified: file.metadata.modified,
+            md5: file.metadata.md5.clone(),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl SyncState {
+    /// Reads the state of the last sync of `left` and `right`, an empty one if there was none.
+    pub async fn load(path: &Path, left: &FileId, right: &FileId) -> Result<Self> {
+        let context = || format!("Could not read sync state '{}'", path.to_string_lossy());
+
+        let json = match tokio::fs::read(path).await {
+            Ok(json) => json,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                return Ok(Self::new(left, right));
+            }
+            Err(e) => return Err(Error::from_io(e, context())),
+        };
+        let state: Self = serde_json::from_slice(&json)
+            .map_err(io::Error::from)
+            .with_context(context)?;
+
+        if (&state.left, &state.right) != (left, right) {
+            return Err(anyhow::anyhow!(
+                "The sync state '{}' is for '{}' and '{}'",
+                path.to_string_lossy(),
+                state.left.1,
+                state.right.1
+            )
+            .into());
+        }
+
+        Ok(state)
+    }
+
+    /// Writes the state to `path`, to be loaded by the next sync.
+    ///
+    /// The state is written to a temporary file first, so a crash while saving cannot leave a
+    /// truncated state behind.
+    pub async fn save(&self, path: &Path) -> Result<()> {
+        let context = || format!("Could not write sync state '{}'", path.to_string_lossy());
+
+        let json = serde_json::to_vec(self)
+            .map_err(io::Error::from)
+            .with_context(context)?;
+        local::write_atomic(path, &json).await.with_context(context)
+    }
+}
+
+impl SyncState {
+    /// The state of two directories that were never synced. Their first sync deletes and renames
+    /// nothing, copies files on only one side to the other, and takes files that differ for
+    /// conflicts.
+    pub fn new(left: &FileId, right: &FileId) -> Self {
+        Self {
+            left: left.clone(),
+            right: right.clone(),
+            entries: BTreeMap::new(),
+        }
+    }
+
+    /// Fails unless this is the state of `left` and `right`.
+    pub(crate) fn check(&self, left: &FileId, right: &FileId) -> Result<()> {
+        if (&self.left, &self.right) != (left, right) {
+            return Err(anyhow::anyhow!(
+                "The sync state is for '{}' and '{}'",
+                self.left.1,
+                self.right.1
+            )
+            .into());
+        }
+
+        Ok(())
+    }
+}
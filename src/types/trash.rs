@@ -0,0 +1,18 @@
+use std::time::SystemTime;
+
+use crate::*;
+
+/// A file in the trash, see [`list_trash`](crate::list_trash).
+#[derive(Debug, Clone)]
+pub struct TrashedFile {
+    /// The file in the trash, its id is what [`restore`](crate::restore) takes. Google Drive files
+    /// keep their ids in the trash, local files are moved into the trash directory.
+    pub file: File,
+    /// The directory the file was trashed from.
+    pub original_parent: Option<FileId>,
+    /// The name of the file before it was trashed, the local trash renames files whose names are
+    /// already taken in it.
+    pub original_name: String,
+    /// When the file was trashed.
+    pub trashed: Option<SystemTime>,
+}
@@ -0,0 +1,25 @@
+use crate::*;
+
+/// How much is stored in a directory, see [`du`](crate::du).
+///
+/// Only the sizes of files count, not those of directories, which are `0` on Google Drive and
+/// whatever the file system uses for them locally.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DiskUsage {
+    /// The total size of the files in bytes.
+    pub size: u64,
+    /// The number of entries that are not directories.
+    pub files: u64,
+    pub dirs: u64,
+}
+
+/// A directory or file with the disk usage of everything in it, see
+/// [`usage_tree`](crate::usage_tree).
+#[derive(Debug, Clone)]
+pub struct UsageTree {
+    pub file: File,
+    /// What is in the directory, for files only their size.
+    pub usage: DiskUsage,
+    /// The largest entries of the directory, largest first.
+    pub children: Vec<UsageTree>,
+}
@@ -0,0 +1,11 @@
+use crate::*;
+
+/// An entry found by [`walk`](crate::walk).
+#[derive(Debug, Clone)]
+pub struct WalkEntry {
+    pub file: File,
+    /// The path of the entry relative to the walked directory, with `/` between names.
+    pub path: String,
+    /// How deep the entry is in the walked directory, `1` for its own entries.
+    pub depth: usize,
+}
@@ -0,0 +1,15 @@
+use crate::*;
+
+/// A change reported by [`watch`](crate::watch).
+#[derive(Debug, Clone)]
+pub enum WatchEvent {
+    /// A file was created in the watched directory, or moved into it.
+    Created(File),
+    /// The contents or the metadata of a file changed.
+    Modified(File),
+    /// A file was deleted, or moved out of the watched directory.
+    Deleted(FileId),
+    /// A file was renamed or moved within the watched directory. Google Drive files keep their
+    /// ids, so there `from` is the id of `to`.
+    Renamed { from: FileId, to: File },
+}